@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS balance_history (
+    uid BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    superseded_by BIGINT DEFAULT 9223372036854775806 NOT NULL,
+    block_uid BIGINT NOT NULL REFERENCES blocks_microblocks (uid) ON DELETE CASCADE,
+    tx_uid BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    asset_id TEXT NOT NULL,
+    amount_before BIGINT NOT NULL,
+    amount_after BIGINT NOT NULL,
+
+    PRIMARY KEY (superseded_by, address, asset_id)
+);
+
+CREATE INDEX IF NOT EXISTS balance_history_block_uid_idx ON balance_history (block_uid);
+CREATE INDEX IF NOT EXISTS balance_history_address_asset_id_uid_idx ON balance_history (address, asset_id, uid);
+CREATE INDEX IF NOT EXISTS balance_history_tx_uid_idx ON balance_history (tx_uid);
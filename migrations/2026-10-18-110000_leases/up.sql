@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS leases (
+    uid BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    superseded_by BIGINT DEFAULT 9223372036854775806 NOT NULL,
+    block_uid BIGINT NOT NULL REFERENCES blocks_microblocks (uid) ON DELETE CASCADE,
+    lease_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    origin_transaction_id TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    sender_public_key TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+
+    PRIMARY KEY (superseded_by, lease_id)
+);
+
+CREATE INDEX IF NOT EXISTS leases_block_uid_idx ON leases (block_uid);
+CREATE INDEX IF NOT EXISTS leases_lease_id_uid_idx ON leases (lease_id, uid);
+CREATE INDEX IF NOT EXISTS leases_sender_status_idx ON leases (sender, status) WHERE (superseded_by = '9223372036854775806'::BIGINT);
+CREATE INDEX IF NOT EXISTS leases_recipient_status_idx ON leases (recipient, status) WHERE (superseded_by = '9223372036854775806'::BIGINT);
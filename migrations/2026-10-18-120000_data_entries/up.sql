@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS data_entries (
+    uid BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    superseded_by BIGINT DEFAULT 9223372036854775806 NOT NULL,
+    block_uid BIGINT NOT NULL REFERENCES blocks_microblocks (uid) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    key TEXT NOT NULL,
+    data_type TEXT,
+    value_integer BIGINT,
+    value_boolean BOOLEAN,
+    value_binary TEXT,
+    value_string TEXT,
+
+    PRIMARY KEY (superseded_by, address, key)
+);
+
+CREATE INDEX IF NOT EXISTS data_entries_block_uid_idx ON data_entries (block_uid);
+CREATE INDEX IF NOT EXISTS data_entries_address_key_uid_idx ON data_entries (address, key, uid);
+CREATE INDEX IF NOT EXISTS data_entries_key_idx ON data_entries (key) WHERE (superseded_by = '9223372036854775806'::BIGINT);
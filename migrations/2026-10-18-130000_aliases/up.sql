@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS aliases (
+    alias TEXT NOT NULL PRIMARY KEY,
+    address TEXT NOT NULL,
+    qualified_alias TEXT NOT NULL,
+    tx_uid BIGINT NOT NULL,
+    block_uid BIGINT NOT NULL REFERENCES blocks_microblocks (uid) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS aliases_block_uid_idx ON aliases (block_uid);
+CREATE INDEX IF NOT EXISTS aliases_address_idx ON aliases (address);
+
+-- Aliases created before the consumer wrote this table are seeded from txs_10, which takes
+-- a full scan of it. Like the consumer, the first successful creation of an alias wins.
+-- Aliases are qualified by the chain id, which is the second byte of every base58 address.
+WITH chain AS (
+    SELECT chr(mod(div(
+        sum((strpos('123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz', substr(a.sender, i, 1)) - 1)
+            * 58::NUMERIC ^ (length(a.sender) - i)),
+        256::NUMERIC ^ 24
+    ), 256)::INTEGER) AS id
+    FROM (SELECT sender FROM txs_10 LIMIT 1) a, generate_series(1, length(a.sender)) i
+)
+INSERT INTO aliases (alias, address, qualified_alias, tx_uid, block_uid)
+SELECT DISTINCT ON (t.alias) t.alias, t.sender, 'alias:' || chain.id || ':' || t.alias, t.uid, t.block_uid
+FROM txs_10 t, chain
+WHERE t.status = 'succeeded'
+ORDER BY t.alias, t.uid
+ON CONFLICT (alias) DO NOTHING;
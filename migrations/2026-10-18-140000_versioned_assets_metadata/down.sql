@@ -0,0 +1,20 @@
+-- The current versions are kept, the superseded ones are dropped.
+ALTER TABLE assets_metadata RENAME TO assets_metadata_versioned;
+ALTER TABLE assets_metadata_versioned
+    RENAME CONSTRAINT assets_metadata_pkey TO assets_metadata_versioned_pkey;
+
+CREATE TABLE IF NOT EXISTS assets_metadata (
+    asset_id VARCHAR,
+    asset_name VARCHAR,
+    ticker VARCHAR,
+    height INTEGER,
+
+    CONSTRAINT asset_meta_pk PRIMARY KEY (asset_id)
+);
+
+INSERT INTO assets_metadata (asset_id, asset_name, ticker, height)
+SELECT asset_id, asset_name, ticker, height
+FROM assets_metadata_versioned
+WHERE superseded_by = 9223372036854775806;
+
+DROP TABLE assets_metadata_versioned;
@@ -0,0 +1,36 @@
+-- The existing rows become the current versions, each under the first block at its height, or
+-- the first stored block for heights that are not stored. The insert fails on the NOT NULL
+-- block_uid when there are rows but no blocks at all.
+ALTER TABLE assets_metadata RENAME TO assets_metadata_unversioned;
+ALTER TABLE assets_metadata_unversioned
+    RENAME CONSTRAINT asset_meta_pk TO asset_meta_unversioned_pk;
+
+CREATE TABLE IF NOT EXISTS assets_metadata (
+    uid BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    superseded_by BIGINT DEFAULT 9223372036854775806 NOT NULL,
+    block_uid BIGINT NOT NULL REFERENCES blocks_microblocks (uid) ON DELETE CASCADE,
+    asset_id VARCHAR NOT NULL,
+    asset_name VARCHAR,
+    ticker VARCHAR,
+    height INTEGER,
+
+    PRIMARY KEY (superseded_by, asset_id)
+);
+
+INSERT INTO assets_metadata (block_uid, asset_id, asset_name, ticker, height)
+SELECT
+    coalesce(b.uid, (SELECT min(uid) FROM blocks_microblocks)),
+    m.asset_id,
+    m.asset_name,
+    m.ticker,
+    m.height
+FROM assets_metadata_unversioned m
+LEFT JOIN (
+    SELECT height, min(uid) AS uid FROM blocks_microblocks GROUP BY height
+) b ON b.height = m.height
+ORDER BY m.height, m.asset_id;
+
+DROP TABLE assets_metadata_unversioned;
+
+CREATE INDEX IF NOT EXISTS assets_metadata_block_uid_idx ON assets_metadata (block_uid);
+CREATE INDEX IF NOT EXISTS assets_metadata_asset_id_uid_idx ON assets_metadata (asset_id, uid);
@@ -0,0 +1,97 @@
+-- Merges the partitions back into plain txs_N tables inheriting txs. Rows are copied.
+
+DROP FUNCTION IF EXISTS create_txs_partitions;
+DROP VIEW IF EXISTS txs;
+
+CREATE TABLE IF NOT EXISTS txs (
+    uid BIGINT NOT NULL,
+    tx_type SMALLINT NOT NULL,
+    sender VARCHAR,
+    sender_public_key VARCHAR,
+    time_stamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    height INTEGER NOT NULL,
+    id VARCHAR NOT NULL,
+    signature VARCHAR,
+    proofs TEXT[],
+    tx_version SMALLINT,
+    fee BIGINT NOT NULL,
+    status VARCHAR DEFAULT 'succeeded' NOT NULL,
+    block_uid BIGINT NOT NULL,
+
+    CONSTRAINT txs_pk_uid_id_time_stamp PRIMARY KEY (uid, id, time_stamp),
+    CONSTRAINT fk_blocks_uid FOREIGN KEY (block_uid) REFERENCES blocks_microblocks(uid)
+);
+
+CREATE INDEX IF NOT EXISTS txs_height_idx ON txs USING btree (height);
+CREATE INDEX IF NOT EXISTS txs_sender_uid_idx ON txs USING btree (sender, uid);
+CREATE INDEX IF NOT EXISTS txs_id_idx ON txs USING hash (id);
+CREATE INDEX IF NOT EXISTS txs_time_stamp_uid_gist_idx ON txs USING gist (time_stamp, uid);
+CREATE INDEX IF NOT EXISTS txs_tx_type_idx ON txs USING btree (tx_type);
+CREATE INDEX IF NOT EXISTS txs_time_stamp_uid_idx ON txs USING btree (time_stamp, uid);
+
+ALTER TABLE txs_11_transfers DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_12_data DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_16_args DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_16_payment DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_18_args DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_18_payment DROP CONSTRAINT fk_tx_uid;
+
+CREATE FUNCTION pg_temp.unpartition_txs_table(tbl TEXT)
+RETURNS VOID
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    partitioned TEXT := tbl || '_partitioned';
+    idx RECORD;
+BEGIN
+    EXECUTE format('ALTER TABLE %I RENAME TO %I', tbl, partitioned);
+    EXECUTE format(
+        'CREATE TABLE %I (LIKE %I INCLUDING DEFAULTS INCLUDING CONSTRAINTS) INHERITS (txs)',
+        tbl, partitioned
+    );
+    EXECUTE format('INSERT INTO %I SELECT * FROM %I', tbl, partitioned);
+
+    EXECUTE format('ALTER TABLE %I ADD CONSTRAINT %I PRIMARY KEY (uid)', tbl, tbl || '_pk_uid');
+    EXECUTE format(
+        'ALTER TABLE %I ADD CONSTRAINT fk_blocks_uid FOREIGN KEY (block_uid) REFERENCES blocks_microblocks(uid)',
+        tbl
+    );
+
+    FOR idx IN
+        SELECT c.relname AS name, pg_get_indexdef(c.oid) AS def
+        FROM pg_index i JOIN pg_class c ON c.oid = i.indexrelid
+        WHERE i.indrelid = partitioned::regclass AND NOT i.indisunique
+    LOOP
+        EXECUTE format('DROP INDEX %I', idx.name);
+        EXECUTE regexp_replace(idx.def, ' ON (ONLY )?(\S+\.)?' || partitioned || ' ', ' ON ' || tbl || ' ');
+    END LOOP;
+
+    EXECUTE format('DROP TABLE %I', partitioned);
+    -- the first partition had it
+    EXECUTE format(
+        'CREATE UNIQUE INDEX %I ON %I (uid, time_stamp)', tbl || '_uid_time_stamp_unique_idx', tbl
+    );
+END;
+$$;
+
+DO $$
+BEGIN
+    FOR n IN 1..18 LOOP
+        PERFORM pg_temp.unpartition_txs_table('txs_' || n);
+    END LOOP;
+END;
+$$;
+
+ALTER TABLE txs_9 ADD CONSTRAINT txs_9_un UNIQUE (uid, lease_tx_uid);
+
+ALTER TABLE txs_16_args ALTER COLUMN height DROP NOT NULL;
+ALTER TABLE txs_16_payment ALTER COLUMN height DROP NOT NULL;
+ALTER TABLE txs_18_args ALTER COLUMN height DROP NOT NULL;
+ALTER TABLE txs_18_payment ALTER COLUMN height DROP NOT NULL;
+
+ALTER TABLE txs_11_transfers ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_11(uid) ON DELETE CASCADE;
+ALTER TABLE txs_12_data ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_12(uid) ON DELETE CASCADE;
+ALTER TABLE txs_16_args ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_16(uid) ON DELETE CASCADE;
+ALTER TABLE txs_16_payment ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_16(uid) ON DELETE CASCADE;
+ALTER TABLE txs_18_args ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_18(uid) ON DELETE CASCADE;
+ALTER TABLE txs_18_payment ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid) REFERENCES txs_18(uid) ON DELETE CASCADE;
@@ -0,0 +1,167 @@
+-- txs_N tables become partitioned by height range.
+--
+-- Partitioned tables can't take part in inheritance, so txs becomes a view over all of them.
+-- Existing rows aren't copied: each table is attached as the first partition of its successor,
+-- up to the next multiple of 100000 heights, and its indexes are reused by the successor's ones.
+-- Unique indexes must contain the partition key, so the primary keys are rebuilt as (uid, height)
+-- and height is added to the other unique indexes. Building them reads and sorts all the existing
+-- rows of every table while it's locked, which takes about as long as a REINDEX of the old ones.
+-- Further partitions are created by the consumer ahead of the ingested height.
+
+-- references must contain the partition key as well, so they're recreated below
+ALTER TABLE txs_11_transfers DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_12_data DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_16_args DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_16_payment DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_18_args DROP CONSTRAINT fk_tx_uid;
+ALTER TABLE txs_18_payment DROP CONSTRAINT fk_tx_uid;
+
+CREATE FUNCTION pg_temp.partition_txs_table(tbl TEXT, bound INTEGER)
+RETURNS VOID
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    first_partition TEXT := tbl || '_0';
+    pk TEXT := (SELECT conname FROM pg_constraint WHERE conrelid = tbl::regclass AND contype = 'p');
+    idx RECORD;
+    cols TEXT;
+BEGIN
+    EXECUTE format('ALTER TABLE %I NO INHERIT txs', tbl);
+    EXECUTE format('ALTER TABLE %I DROP CONSTRAINT %I', tbl, pk);
+    EXECUTE format('ALTER TABLE %I RENAME TO %I', tbl, first_partition);
+
+    EXECUTE format(
+        'CREATE TABLE %I (LIKE %I INCLUDING DEFAULTS INCLUDING CONSTRAINTS) PARTITION BY RANGE (height)',
+        tbl, first_partition
+    );
+    EXECUTE format('ALTER TABLE %I ADD CONSTRAINT %I PRIMARY KEY (uid, height)', tbl, tbl || '_pk_uid_height');
+    EXECUTE format(
+        'ALTER TABLE %I ADD CONSTRAINT fk_blocks_uid FOREIGN KEY (block_uid) REFERENCES blocks_microblocks(uid)',
+        tbl
+    );
+
+    -- unique ones are rebuilt with height once the first partition is attached
+    FOR idx IN
+        SELECT c.relname AS name, pg_get_indexdef(c.oid) AS def
+        FROM pg_index i JOIN pg_class c ON c.oid = i.indexrelid
+        WHERE i.indrelid = first_partition::regclass AND NOT i.indisunique
+    LOOP
+        EXECUTE format('ALTER INDEX %I RENAME TO %I', idx.name, idx.name || '_0');
+        EXECUTE regexp_replace(idx.def, ' ON (\S+\.)?' || first_partition || ' ', ' ON ' || tbl || ' ');
+    END LOOP;
+
+    -- attaching skips scanning the rows for the partition bound, if a valid constraint implies it
+    EXECUTE format(
+        'ALTER TABLE %I ADD CONSTRAINT %I CHECK (height < %s) NOT VALID',
+        first_partition, first_partition || '_bound', bound
+    );
+    EXECUTE format('ALTER TABLE %I VALIDATE CONSTRAINT %I', first_partition, first_partition || '_bound');
+    EXECUTE format(
+        'ALTER TABLE %I ATTACH PARTITION %I FOR VALUES FROM (MINVALUE) TO (%s)',
+        tbl, first_partition, bound
+    );
+    EXECUTE format('ALTER TABLE %I DROP CONSTRAINT %I', first_partition, first_partition || '_bound');
+
+    FOR idx IN
+        SELECT c.relname AS name, pg_get_indexdef(c.oid) AS def, con.oid IS NOT NULL AS is_constraint
+        FROM pg_index i
+        JOIN pg_class c ON c.oid = i.indexrelid
+        LEFT JOIN pg_constraint con ON con.conindid = c.oid AND con.conrelid = i.indrelid
+        WHERE i.indrelid = first_partition::regclass AND i.indisunique AND NOT i.indisprimary
+    LOOP
+        cols := substring(idx.def FROM '\((.*)\)$') || ', height';
+        IF idx.is_constraint THEN
+            EXECUTE format('ALTER TABLE %I DROP CONSTRAINT %I', first_partition, idx.name);
+            EXECUTE format('ALTER TABLE %I ADD CONSTRAINT %I UNIQUE (%s)', tbl, idx.name, cols);
+        ELSE
+            EXECUTE format('DROP INDEX %I', idx.name);
+            EXECUTE format('CREATE UNIQUE INDEX %I ON %I (%s)', idx.name, tbl, cols);
+        END IF;
+    END LOOP;
+END;
+$$;
+
+DO $$
+DECLARE
+    bound INTEGER := (SELECT (COALESCE(max(height), 0) / 100000 + 1) * 100000 FROM blocks_microblocks);
+BEGIN
+    FOR n IN 1..18 LOOP
+        PERFORM pg_temp.partition_txs_table('txs_' || n, bound);
+    END LOOP;
+END;
+$$;
+
+DROP TABLE txs;
+
+CREATE VIEW txs AS
+    SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_1
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_2
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_3
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_4
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_5
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_6
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_7
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_8
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_9
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_10
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_11
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_12
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_13
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_14
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_15
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_16
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_17
+    UNION ALL SELECT uid, tx_type, sender, sender_public_key, time_stamp, height, id, signature, proofs, tx_version, fee, status, block_uid FROM txs_18;
+
+-- references with a NULL column aren't checked, so heights are filled in from the transactions
+UPDATE txs_16_args a SET height = t.height FROM txs_16 t WHERE t.uid = a.tx_uid AND a.height IS NULL;
+UPDATE txs_16_payment p SET height = t.height FROM txs_16 t WHERE t.uid = p.tx_uid AND p.height IS NULL;
+UPDATE txs_18_args a SET height = t.height FROM txs_18 t WHERE t.uid = a.tx_uid AND a.height IS NULL;
+UPDATE txs_18_payment p SET height = t.height FROM txs_18 t WHERE t.uid = p.tx_uid AND p.height IS NULL;
+ALTER TABLE txs_16_args ALTER COLUMN height SET NOT NULL;
+ALTER TABLE txs_16_payment ALTER COLUMN height SET NOT NULL;
+ALTER TABLE txs_18_args ALTER COLUMN height SET NOT NULL;
+ALTER TABLE txs_18_payment ALTER COLUMN height SET NOT NULL;
+
+-- existing rows were already checked against the first partitions
+ALTER TABLE txs_11_transfers ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_11 (uid, height) ON DELETE CASCADE NOT VALID;
+ALTER TABLE txs_12_data ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_12 (uid, height) ON DELETE CASCADE NOT VALID;
+ALTER TABLE txs_16_args ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_16 (uid, height) ON DELETE CASCADE NOT VALID;
+ALTER TABLE txs_16_payment ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_16 (uid, height) ON DELETE CASCADE NOT VALID;
+ALTER TABLE txs_18_args ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_18 (uid, height) ON DELETE CASCADE NOT VALID;
+ALTER TABLE txs_18_payment ADD CONSTRAINT fk_tx_uid FOREIGN KEY (tx_uid, height) REFERENCES txs_18 (uid, height) ON DELETE CASCADE NOT VALID;
+
+-- Appends partitions of `partition_size` heights to every txs_N table,
+-- until heights up to `up_to_height` are covered. Returns the height all of them
+-- have partitions below.
+CREATE OR REPLACE FUNCTION create_txs_partitions(up_to_height INTEGER, partition_size INTEGER)
+RETURNS INTEGER
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    tbl TEXT;
+    bound INTEGER;
+    covered INTEGER;
+BEGIN
+    FOR n IN 1..18 LOOP
+        tbl := 'txs_' || n;
+
+        SELECT max(substring(pg_get_expr(c.relpartbound, c.oid) FROM 'TO \((\d+)\)')::INTEGER)
+        INTO bound
+        FROM pg_inherits i JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE i.inhparent = tbl::regclass;
+
+        WHILE bound <= up_to_height LOOP
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF %I FOR VALUES FROM (%s) TO (%s)',
+                tbl || '_' || bound, tbl, bound, bound + partition_size
+            );
+            bound := bound + partition_size;
+        END LOOP;
+
+        covered := LEAST(covered, bound);
+    END LOOP;
+
+    RETURN covered;
+END;
+$$;
@@ -0,0 +1,12 @@
+-- Outbox of ingested changes, written in the same transaction as the changes themselves.
+-- The consumer is the only writer, so seq follows the commit order and indexers
+-- can resume from the last seq they have seen. Rollbacks don't remove events,
+-- they are recorded as events listing the removed block_uids.
+CREATE TABLE IF NOT EXISTS ingest_events (
+    seq BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    block_uid BIGINT NOT NULL,
+    height INTEGER NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
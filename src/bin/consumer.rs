@@ -1,17 +1,23 @@
 use anyhow::{Context, Result};
-use app_lib::{config, consumer, db};
+use app_lib::{config, consumer, db, metrics};
+use std::process::ExitCode;
 use std::time::Duration;
 use tokio::select;
+use tokio::signal::unix::{signal, SignalKind};
 use wavesexchange_liveness::channel;
 use wavesexchange_log::{error, info};
 use wavesexchange_warp::MetricsWarpBuilder;
 
 const LAST_TIMESTAMP_QUERY: &str = "SELECT (EXTRACT(EPOCH FROM time_stamp) * 1000)::BIGINT as time_stamp FROM blocks_microblocks WHERE time_stamp IS NOT NULL ORDER BY uid DESC LIMIT 1";
+// with deferred candles, not ready while there are exchanges after the last minute candle
+const LAST_TIMESTAMP_WITH_CANDLES_QUERY: &str = "SELECT (EXTRACT(EPOCH FROM time_stamp) * 1000)::BIGINT as time_stamp FROM blocks_microblocks WHERE time_stamp IS NOT NULL AND NOT EXISTS (SELECT 1 FROM txs_7 WHERE time_stamp >= COALESCE((SELECT max(time_start) FROM candles WHERE interval = '1m'), '-infinity') + INTERVAL '1 minute') ORDER BY uid DESC LIMIT 1";
 const POLL_INTERVAL_SECS: u64 = 60;
 const MAX_BLOCK_AGE: Duration = Duration::from_secs(300);
+// 128 + SIGTERM, distinguishes a graceful shutdown from a failure
+const SHUTDOWN_EXIT_CODE: u8 = 143;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let config = config::load_consumer_config()?;
 
     info!(
@@ -23,34 +29,108 @@ async fn main() -> Result<()> {
         .await
         .context("DB connection failed")?;
 
-    let updates_src = consumer::updates::new(&config.consumer.blockchain_updates_url)
-        .await
-        .context("Blockchain connection failed")?;
-
-    let pg_repo = consumer::repo::pg::new(conn);
+    let pg_repo = consumer::repo::pg::new(
+        conn,
+        config.consumer.copy_threshold,
+        config.consumer.txs_partition_size.get() as i32,
+    );
 
     let db_url = config.postgres.database_url();
+    let last_timestamp_query = if config.consumer.profile.candles
+        && config.consumer.candles_defer_blocks_behind.is_some()
+    {
+        LAST_TIMESTAMP_WITH_CANDLES_QUERY
+    } else {
+        LAST_TIMESTAMP_QUERY
+    };
     let readiness_channel = channel(
         db_url,
         POLL_INTERVAL_SECS,
         MAX_BLOCK_AGE,
-        Some(LAST_TIMESTAMP_QUERY.to_string()),
+        Some(last_timestamp_query.to_string()),
     );
 
     let metrics = tokio::spawn(async move {
         MetricsWarpBuilder::new()
             .with_metrics_port(config.consumer.metrics_port)
+            .with_metric(&*metrics::ACTIVE_UPDATES_ENDPOINT)
+            .with_metric(&*metrics::LAST_COMMITTED_HEIGHT)
+            .with_metric(&*metrics::BLOCK_TIMESTAMP_LAG)
+            .with_metric(&*metrics::UPDATES_PER_BATCH)
+            .with_metric(&*metrics::TXS_INGESTED)
+            .with_metric(&*metrics::TXS_SKIPPED)
+            .with_metric(&*metrics::ROLLBACKS)
+            .with_metric(&*metrics::ROLLBACK_DEPTH)
+            .with_metric(&*metrics::DB_TRANSACTION_DURATION)
+            .with_metric(&*metrics::CANDLES_CALCULATION_DURATION)
+            .with_metric(&*metrics::CANDLES_DEFERRED)
             .with_readiness_channel(readiness_channel)
             .run_async()
             .await
     });
 
-    let consumer = consumer::start(updates_src, pg_repo, config.consumer);
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let shutdown = async move {
+        select! {
+            _ = sigterm.recv() => info!("SIGTERM received"),
+            _ = sigint.recv() => info!("SIGINT received"),
+        }
+    };
+
+    let consumer_config = config.consumer;
+    let consumer = async move {
+        match consumer_config.updates_file_path.clone() {
+            Some(path) => {
+                info!("Replaying updates from {}", path);
+                let updates_src =
+                    consumer::updates_file::new(&path).context("Cannot open updates file")?;
+                run(updates_src, pg_repo, consumer_config, shutdown).await
+            }
+            None => {
+                let recorder = match &consumer_config.record_updates_dir {
+                    Some(dir) => {
+                        info!("Recording updates into {}", dir);
+                        Some(
+                            consumer::updates_archive::ArchiveWriter::new(
+                                dir,
+                                consumer_config.record_max_file_size,
+                            )
+                            .context("Cannot create updates archive")?,
+                        )
+                    }
+                    None => None,
+                };
+                let updates_src = consumer::updates::new(
+                    &consumer_config.blockchain_updates_urls,
+                    consumer_config.updates_stale_timeout,
+                    recorder,
+                )
+                .await
+                .context("Blockchain connection failed")?;
+                let result = run(updates_src.clone(), pg_repo, consumer_config, shutdown).await;
+                // the index only lists finished files, so the last one is finished before exiting
+                updates_src.finish_recording();
+                result
+            }
+        }
+    };
 
     select! {
-        Err(err) = consumer => {
-            error!("{}", err);
-            return Err(err);
+        result = consumer => {
+            match result {
+                Err(err) => {
+                    error!("{}", err);
+                    return Err(err);
+                }
+                Ok(consumer::Stopped::TargetHeightReached(summary)) => {
+                    info!("Consumer reached target height: {}", summary);
+                }
+                Ok(consumer::Stopped::Shutdown) => {
+                    info!("Consumer stopped gracefully");
+                    return Ok(ExitCode::from(SHUTDOWN_EXIT_CODE));
+                }
+            }
         },
         result = metrics => {
             if let Err(err) = result {
@@ -60,5 +140,24 @@ async fn main() -> Result<()> {
             }
         }
     };
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Backfills by segments when configured, otherwise follows the chain
+async fn run<T, R, S>(
+    updates_src: T,
+    repo: R,
+    config: config::consumer::Config,
+    shutdown: S,
+) -> Result<consumer::Stopped>
+where
+    T: consumer::UpdatesSource + Clone + Send + 'static,
+    R: consumer::repo::Repo + Clone + Send + 'static,
+    S: std::future::Future<Output = ()> + Send,
+{
+    if config.backfill_segment_size.is_some() {
+        consumer::backfill::start(updates_src, repo, config, shutdown).await
+    } else {
+        consumer::start(updates_src, repo, config, shutdown).await
+    }
 }
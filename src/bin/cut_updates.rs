@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use prost::Message;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use app_lib::consumer::updates_archive::{read_index, read_updates_file, INDEX_FILE_NAME};
+
+const USAGE: &str = "usage: cut_updates <archive dir> <from height> <to height> <output file>";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [archive_dir, from_height, to_height, output] = args.as_slice() else {
+        anyhow::bail!(USAGE);
+    };
+    let from_height: i32 = from_height.parse().context(USAGE)?;
+    let to_height: i32 = to_height.parse().context(USAGE)?;
+    let archive_dir = Path::new(archive_dir);
+
+    let files = archive_files(archive_dir, from_height, to_height)?;
+
+    let output_file = BufWriter::new(File::create(output)?);
+    let mut writer: Box<dyn Write> = if output.ends_with(".gz") {
+        Box::new(GzEncoder::new(output_file, Compression::default()))
+    } else {
+        Box::new(output_file)
+    };
+
+    let mut written = 0;
+    for file in files {
+        for update in read_updates_file(&file)? {
+            if update.height >= from_height && update.height <= to_height {
+                writer.write_all(&update.encode_length_delimited_to_vec())?;
+                written += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+
+    println!(
+        "Written {} updates at heights {}..={} into {}",
+        written, from_height, to_height, output
+    );
+    Ok(())
+}
+
+/// Files that may contain the height range, in archive order
+fn archive_files(dir: &Path, from_height: i32, to_height: i32) -> Result<Vec<PathBuf>> {
+    if dir.join(INDEX_FILE_NAME).exists() {
+        let mut entries = read_index(dir)?;
+        entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        return Ok(entries
+            .into_iter()
+            .filter(|e| e.first_height <= to_height && e.last_height >= from_height)
+            .map(|e| dir.join(e.file_name))
+            .collect());
+    }
+
+    // no index (e.g. the recorder was killed), scan every file
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|path| path.is_file() && !path.ends_with(INDEX_FILE_NAME));
+    files.sort();
+    Ok(files)
+}
@@ -1,7 +1,8 @@
 use crate::error::Error;
 use chrono::Duration;
 use serde::Deserialize;
-use std::num::NonZeroU32;
+use std::collections::HashSet;
+use std::num::{NonZeroU32, NonZeroUsize};
 
 fn default_assets_only() -> bool {
     false
@@ -27,16 +28,176 @@ fn default_metrics_port() -> u16 {
     9090
 }
 
+fn default_reconnect_initial_delay_in_msecs() -> u64 {
+    1000
+}
+
+fn default_reconnect_max_delay_in_msecs() -> u64 {
+    60000
+}
+
+fn default_updates_stale_timeout_in_secs() -> u64 {
+    120
+}
+
+fn default_resubscribe_rollback_depth() -> u32 {
+    1
+}
+
+fn default_record_max_file_size_mb() -> usize {
+    256
+}
+
+fn default_backfill_concurrency() -> usize {
+    4
+}
+
+fn default_copy_threshold() -> usize {
+    5000
+}
+
+fn default_txs_partition_size() -> u32 {
+    100000
+}
+
+fn default_ingest_tx_types() -> String {
+    "all".into()
+}
+
+fn default_ingest_true() -> bool {
+    true
+}
+
+fn default_ingest_events() -> bool {
+    IngestionProfile::FULL.events
+}
+
+fn default_asset_oracle_key_patterns() -> String {
+    "name:asset_name,ticker:ticker".into()
+}
+
+/// `assets_metadata` column filled from an asset storage oracle data entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetMetadataField {
+    AssetName,
+    Ticker,
+}
+
+/// Maps oracle keys like `<key>_<assetId>` to an `assets_metadata` column
+#[derive(Debug, Clone)]
+pub struct AssetOracleKeyPattern {
+    pub key: String,
+    pub field: AssetMetadataField,
+}
+
+/// What the consumer writes: transactions of the given types and the toggled tables.
+///
+/// State derived from transactions follows their types: aliases are ingested with
+/// `CreateAlias` (10), leases with `Lease` (8), data entries with `DataTransaction` (12),
+/// and balance history with the stored transactions, after the allow list as well.
+/// Squashes and rollbacks don't depend on the profile, as rows written with another one
+/// must still follow the blocks. Candles and pairs aren't tied to blocks, so they are
+/// rolled back and recalculated only with `candles`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IngestionProfile {
+    /// indexed by transaction type, 1..=18
+    pub tx_types: [bool; 19],
+    /// `asset_updates` and `asset_origins`
+    pub assets: bool,
+    /// `asset_tickers` and `assets_metadata` from the asset storage oracle
+    pub tickers: bool,
+    /// `candles` and `pairs`, require exchange transactions
+    pub candles: bool,
+    pub waves_data: bool,
+    /// `ingest_events` outbox of the other tables' changes and of rollbacks
+    pub events: bool,
+}
+
+impl IngestionProfile {
+    /// Every table, as with the default config. The `ingest_events` outbox is opt-in,
+    /// since nothing prunes it.
+    pub const FULL: IngestionProfile = IngestionProfile {
+        tx_types: [true; 19],
+        assets: true,
+        tickers: true,
+        candles: true,
+        waves_data: true,
+        events: false,
+    };
+
+    pub const ASSETS_ONLY: IngestionProfile = IngestionProfile {
+        tx_types: [false; 19],
+        assets: true,
+        tickers: true,
+        candles: false,
+        waves_data: false,
+        events: false,
+    };
+
+    pub fn tx_type(&self, tx_type: i16) -> bool {
+        self.tx_types
+            .get(tx_type as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn any_txs(&self) -> bool {
+        self.tx_types.iter().any(|&t| t)
+    }
+
+    pub fn leases(&self) -> bool {
+        self.tx_type(8)
+    }
+
+    pub fn data_entries(&self) -> bool {
+        self.tx_type(12)
+    }
+}
+
+/// Keeps only transactions whose sender, recipients, dApp, order senders
+/// or assets are listed. Assets themselves are not filtered, so candles of the kept
+/// exchanges can always resolve decimals.
+#[derive(Debug, Clone, Default)]
+pub struct TxAllowList {
+    pub addresses: HashSet<String>,
+    pub assets: HashSet<String>,
+}
+
+impl TxAllowList {
+    pub fn allows<'a>(
+        &self,
+        addresses: impl IntoIterator<Item = &'a str>,
+        asset_ids: impl IntoIterator<Item = &'a str>,
+    ) -> bool {
+        addresses.into_iter().any(|a| self.addresses.contains(a))
+            || asset_ids.into_iter().any(|a| self.assets.contains(a))
+    }
+}
+
 #[derive(Deserialize)]
 struct ConfigFlat {
     asset_storage_address: Option<String>,
+    /// shortcut for `IngestionProfile::ASSETS_ONLY`, kept for existing deployments
     #[serde(default = "default_assets_only")]
     assets_only: bool,
+    #[serde(default = "default_ingest_tx_types")]
+    ingest_tx_types: String,
+    #[serde(default = "default_ingest_true")]
+    ingest_assets: bool,
+    #[serde(default = "default_ingest_true")]
+    ingest_tickers: bool,
+    #[serde(default = "default_ingest_true")]
+    ingest_candles: bool,
+    #[serde(default = "default_ingest_true")]
+    ingest_waves_data: bool,
+    #[serde(default = "default_ingest_events")]
+    ingest_events: bool,
     blockchain_updates_url: String,
     chain_id: u8,
     #[serde(default = "default_max_wait_time_in_msecs")]
     max_wait_time_in_msecs: u64,
     starting_height: u32,
+    target_height: Option<u32>,
     #[serde(default = "default_updates_per_request")]
     updates_per_request: usize,
     #[serde(default = "default_start_rollback_depth")]
@@ -45,20 +206,78 @@ struct ConfigFlat {
     rollback_step: u32,
     #[serde(default = "default_metrics_port")]
     metrics_port: u16,
+    #[serde(default = "default_asset_oracle_key_patterns")]
+    asset_oracle_key_patterns: String,
+    #[serde(default = "default_reconnect_initial_delay_in_msecs")]
+    reconnect_initial_delay_in_msecs: u64,
+    #[serde(default = "default_reconnect_max_delay_in_msecs")]
+    reconnect_max_delay_in_msecs: u64,
+    #[serde(default = "default_updates_stale_timeout_in_secs")]
+    updates_stale_timeout_in_secs: u64,
+    #[serde(default = "default_resubscribe_rollback_depth")]
+    resubscribe_rollback_depth: u32,
+    updates_file_path: Option<String>,
+    record_updates_dir: Option<String>,
+    #[serde(default = "default_record_max_file_size_mb")]
+    record_max_file_size_mb: usize,
+    backfill_segment_size: Option<u32>,
+    #[serde(default = "default_backfill_concurrency")]
+    backfill_concurrency: usize,
+    #[serde(default = "default_copy_threshold")]
+    copy_threshold: usize,
+    #[serde(default = "default_txs_partition_size")]
+    txs_partition_size: u32,
+    candles_defer_blocks_behind: Option<u32>,
+    allowed_addresses: Option<String>,
+    allowed_assets: Option<String>,
+    notify_channel: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub asset_storage_address: Option<String>,
-    pub assets_only: bool,
-    pub blockchain_updates_url: String,
+    pub profile: IngestionProfile,
+    /// endpoints in failover order, configured as a comma-separated list
+    pub blockchain_updates_urls: Vec<String>,
     pub chain_id: u8,
     pub max_wait_time: Duration,
     pub starting_height: u32,
+    /// bounded sync: stop after committing this height instead of following the chain
+    pub target_height: Option<u32>,
     pub updates_per_request: usize,
     pub start_rollback_depth: NonZeroU32,
     pub rollback_step: NonZeroU32,
     pub metrics_port: u16,
+    pub asset_oracle_key_patterns: Vec<AssetOracleKeyPattern>,
+    pub reconnect_initial_delay: Duration,
+    pub reconnect_max_delay: Duration,
+    /// endpoint is switched when it sends no updates for this long
+    pub updates_stale_timeout: Duration,
+    /// should cover forks between endpoints, since switching may land on another fork
+    pub resubscribe_rollback_depth: NonZeroU32,
+    /// replay recorded updates from a file or directory instead of blockchain updates endpoints
+    pub updates_file_path: Option<String>,
+    /// archive the raw updates stream into this directory
+    pub record_updates_dir: Option<String>,
+    /// uncompressed size after which an archive file is rotated
+    pub record_max_file_size: usize,
+    /// backfill up to `target_height` by segments of this many heights
+    pub backfill_segment_size: Option<NonZeroU32>,
+    /// segments streamed at once, each one is buffered in memory until written
+    pub backfill_concurrency: NonZeroUsize,
+    /// rows inserted into one table at once above which binary COPY is used instead of INSERT
+    pub copy_threshold: usize,
+    /// heights per txs_N partition, the next one is created before it is reached
+    pub txs_partition_size: NonZeroU32,
+    /// skip candles while the last block is older than this many (1 minute) block intervals,
+    /// and rebuild them once on reaching the tip
+    pub candles_defer_blocks_behind: Option<u32>,
+    /// configured as comma-separated `allowed_addresses` and `allowed_assets`,
+    /// all transactions are kept when neither is set
+    pub tx_allow_list: Option<TxAllowList>,
+    /// `NOTIFY` this channel with a JSON summary of every committed batch, of rollbacks
+    /// before (re)subscribing and of the squashed tip at the end of a bounded sync
+    pub notify_channel: Option<String>,
 }
 
 pub fn load() -> Result<Config, Error> {
@@ -66,18 +285,157 @@ pub fn load() -> Result<Config, Error> {
     let nonzero_err =
         |msg| Error::LoadConfigFailed(envy::Error::Custom(format!("{msg} must be > 0")));
 
+    let blockchain_updates_urls: Vec<String> = config_flat
+        .blockchain_updates_url
+        .split(',')
+        .map(|url| url.trim().to_owned())
+        .filter(|url| !url.is_empty())
+        .collect();
+    if blockchain_updates_urls.is_empty() {
+        return Err(Error::LoadConfigFailed(envy::Error::Custom(
+            "blockchain_updates_url must contain at least one url".to_string(),
+        )));
+    }
+
+    let profile = if config_flat.assets_only {
+        IngestionProfile::ASSETS_ONLY
+    } else {
+        let profile = IngestionProfile {
+            tx_types: parse_tx_types(&config_flat.ingest_tx_types)?,
+            assets: config_flat.ingest_assets,
+            tickers: config_flat.ingest_tickers,
+            candles: config_flat.ingest_candles,
+            waves_data: config_flat.ingest_waves_data,
+            events: config_flat.ingest_events,
+        };
+        if profile.candles && !profile.tx_type(7) {
+            return Err(Error::LoadConfigFailed(envy::Error::Custom(
+                "ingest_candles requires exchange transactions (7) in ingest_tx_types".to_string(),
+            )));
+        }
+        profile
+    };
+
+    let backfill_segment_size = match config_flat.backfill_segment_size {
+        Some(size) => {
+            if config_flat.target_height.is_none() {
+                return Err(Error::LoadConfigFailed(envy::Error::Custom(
+                    "backfill_segment_size requires target_height".to_string(),
+                )));
+            }
+            Some(NonZeroU32::new(size).ok_or_else(|| nonzero_err("backfill_segment_size"))?)
+        }
+        None => None,
+    };
+
+    let tx_allow_list = match (&config_flat.allowed_addresses, &config_flat.allowed_assets) {
+        (None, None) => None,
+        (addresses, assets) => Some(TxAllowList {
+            addresses: parse_list(addresses.as_deref().unwrap_or_default()),
+            assets: parse_list(assets.as_deref().unwrap_or_default()),
+        }),
+    };
+
     Ok(Config {
         asset_storage_address: config_flat.asset_storage_address,
-        assets_only: config_flat.assets_only,
-        blockchain_updates_url: config_flat.blockchain_updates_url,
+        profile,
+        blockchain_updates_urls,
         chain_id: config_flat.chain_id,
         max_wait_time: Duration::milliseconds(config_flat.max_wait_time_in_msecs as i64),
         starting_height: config_flat.starting_height,
+        target_height: config_flat.target_height,
         updates_per_request: config_flat.updates_per_request,
         start_rollback_depth: NonZeroU32::new(config_flat.start_rollback_depth)
             .ok_or_else(|| nonzero_err("start_rollback_depth"))?,
         rollback_step: NonZeroU32::new(config_flat.rollback_step)
             .ok_or_else(|| nonzero_err("rollback_step"))?,
         metrics_port: config_flat.metrics_port,
+        asset_oracle_key_patterns: parse_asset_oracle_key_patterns(
+            &config_flat.asset_oracle_key_patterns,
+        )?,
+        reconnect_initial_delay: Duration::milliseconds(
+            config_flat.reconnect_initial_delay_in_msecs as i64,
+        ),
+        reconnect_max_delay: Duration::milliseconds(
+            config_flat.reconnect_max_delay_in_msecs as i64,
+        ),
+        updates_stale_timeout: Duration::seconds(config_flat.updates_stale_timeout_in_secs as i64),
+        resubscribe_rollback_depth: NonZeroU32::new(config_flat.resubscribe_rollback_depth)
+            .ok_or_else(|| nonzero_err("resubscribe_rollback_depth"))?,
+        updates_file_path: config_flat.updates_file_path,
+        record_updates_dir: config_flat.record_updates_dir,
+        record_max_file_size: config_flat.record_max_file_size_mb * 1024 * 1024,
+        backfill_segment_size,
+        backfill_concurrency: NonZeroUsize::new(config_flat.backfill_concurrency)
+            .ok_or_else(|| nonzero_err("backfill_concurrency"))?,
+        copy_threshold: config_flat.copy_threshold,
+        txs_partition_size: NonZeroU32::new(config_flat.txs_partition_size)
+            .ok_or_else(|| nonzero_err("txs_partition_size"))?,
+        candles_defer_blocks_behind: config_flat.candles_defer_blocks_behind,
+        tx_allow_list,
+        notify_channel: config_flat.notify_channel,
     })
 }
+
+/// Parses comma-separated `key:column` pairs, e.g. `name:asset_name,ticker:ticker`
+fn parse_asset_oracle_key_patterns(patterns: &str) -> Result<Vec<AssetOracleKeyPattern>, Error> {
+    let pattern_err = |msg| Error::LoadConfigFailed(envy::Error::Custom(msg));
+
+    patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (key, column) = p.split_once(':').ok_or_else(|| {
+                pattern_err(format!(
+                    "asset oracle key pattern '{p}' must be 'key:column'"
+                ))
+            })?;
+            let field = match column.trim() {
+                "asset_name" => AssetMetadataField::AssetName,
+                "ticker" => AssetMetadataField::Ticker,
+                other => {
+                    return Err(pattern_err(format!(
+                        "unknown assets_metadata column '{other}' in asset oracle key patterns"
+                    )))
+                }
+            };
+            Ok(AssetOracleKeyPattern {
+                key: key.trim().to_owned(),
+                field,
+            })
+        })
+        .collect()
+}
+
+/// Parses `all`, `none` or comma-separated transaction types, e.g. `4,7,11,16,18`
+fn parse_tx_types(tx_types: &str) -> Result<[bool; 19], Error> {
+    let tx_types_err = |msg| Error::LoadConfigFailed(envy::Error::Custom(msg));
+
+    match tx_types.trim() {
+        "all" => return Ok(IngestionProfile::FULL.tx_types),
+        "none" => return Ok([false; 19]),
+        _ => (),
+    }
+
+    let mut result = [false; 19];
+    for tx_type in tx_types.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        match tx_type.parse::<usize>() {
+            Ok(t) if (1..=18).contains(&t) => result[t] = true,
+            _ => {
+                return Err(tx_types_err(format!(
+                    "unknown transaction type '{tx_type}' in ingest_tx_types"
+                )))
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn parse_list(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
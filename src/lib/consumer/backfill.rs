@@ -0,0 +1,306 @@
+//! Historical backfill: the height range is split into segments which are streamed
+//! and converted concurrently, each by its own subscription, and written in height order.
+//!
+//! Tx uids are derived from heights, so they don't depend on how the range is split.
+//! Candles are calculated once at the end, and microblocks are squashed as in bounded sync.
+
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Instant;
+use tokio::select;
+use tokio::task::JoinHandle;
+use wavesexchange_log::{info, warn};
+
+use super::{
+    finish_sync, handle_updates, repo, rollback_on_start, BatchCommitted, BlockchainUpdate,
+    BlockchainUpdatesWithLastHeight, CandlesMode, Stopped, SyncSummary, UpdatesSource,
+};
+use crate::config::consumer::{AssetOracleKeyPattern, Config, TxAllowList};
+use crate::error::Error as AppError;
+use crate::metrics::{
+    BLOCK_TIMESTAMP_LAG, DB_TRANSACTION_DURATION, LAST_COMMITTED_HEIGHT, UPDATES_PER_BATCH,
+};
+
+/// Heights `from_height..=to_height` fetched by one subscription
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    from_height: u32,
+    to_height: u32,
+}
+
+/// Backfills `starting_height..=target_height` by `backfill_segment_size` segments,
+/// streaming up to `backfill_concurrency` segments ahead of the one being written.
+///
+/// Shutdown is observed between batches, as in `consumer::start`.
+pub async fn start<T, R, S>(updates_src: T, repo: R, config: Config, shutdown: S) -> Result<Stopped>
+where
+    T: UpdatesSource + Clone + Send + 'static,
+    R: repo::Repo + Clone + Send + 'static,
+    S: Future<Output = ()> + Send,
+{
+    let Config {
+        profile,
+        chain_id,
+        max_wait_time,
+        starting_height,
+        target_height,
+        updates_per_request,
+        asset_storage_address,
+        start_rollback_depth,
+        rollback_step,
+        asset_oracle_key_patterns,
+        reconnect_initial_delay,
+        reconnect_max_delay,
+        backfill_segment_size,
+        backfill_concurrency,
+        tx_allow_list,
+        notify_channel,
+        ..
+    } = config;
+
+    let target_height = target_height.ok_or_else(|| anyhow!("backfill requires target_height"))?;
+    let segment_size = backfill_segment_size
+        .ok_or_else(|| anyhow!("backfill requires backfill_segment_size"))?
+        .get();
+
+    let asset_storage_address: Option<&'static str> =
+        asset_storage_address.map(|a| &*Box::leak(a.into_boxed_str()));
+    let asset_oracle_key_patterns: &'static [AssetOracleKeyPattern] =
+        Box::leak(asset_oracle_key_patterns.into_boxed_slice());
+    let tx_allow_list: Option<&'static TxAllowList> =
+        tx_allow_list.map(|l| &*Box::leak(Box::new(l)));
+    let notify_channel: Option<&'static str> =
+        notify_channel.map(|c| &*Box::leak(c.into_boxed_str()));
+
+    let starting_from_height = rollback_on_start(
+        &repo,
+        starting_height,
+        start_rollback_depth,
+        rollback_step,
+        profile,
+        notify_channel,
+    )
+    .await?;
+
+    let mut summary = SyncSummary {
+        from_height: starting_from_height,
+        ..Default::default()
+    };
+    let sync_start = Instant::now();
+
+    let mut segments = segments(starting_from_height, target_height, segment_size).into_iter();
+    info!(
+        "Backfill heights {}..={} by {} segments of {} heights, {} at once",
+        starting_from_height,
+        target_height,
+        segments.len(),
+        segment_size,
+        backfill_concurrency
+    );
+
+    let spawn_fetch = |segment: Segment| {
+        tokio::spawn(fetch_segment(
+            updates_src.clone(),
+            segment,
+            updates_per_request,
+            max_wait_time,
+            reconnect_initial_delay,
+            reconnect_max_delay,
+        ))
+    };
+
+    let mut pending: VecDeque<JoinHandle<Vec<BlockchainUpdatesWithLastHeight>>> = segments
+        .by_ref()
+        .take(backfill_concurrency.get())
+        .map(&spawn_fetch)
+        .collect();
+
+    tokio::pin!(shutdown);
+
+    while let Some(mut fetch) = pending.pop_front() {
+        let batches = select! {
+            biased;
+            _ = &mut shutdown => {
+                info!("Shutdown requested, stop backfill");
+                fetch.abort();
+                pending.iter().for_each(|f| f.abort());
+                return Ok(Stopped::Shutdown);
+            }
+            batches = &mut fetch => batches?,
+        };
+
+        if let Some(segment) = segments.next() {
+            pending.push_back(spawn_fetch(segment));
+        }
+
+        for updates_with_height in batches {
+            select! {
+                biased;
+                _ = &mut shutdown => {
+                    info!("Shutdown requested, stop backfill");
+                    pending.iter().for_each(|f| f.abort());
+                    return Ok(Stopped::Shutdown);
+                }
+                _ = async {} => {}
+            }
+
+            let updates_count = updates_with_height.updates.len();
+            let last_height = updates_with_height.last_height;
+            let last_block_timestamp =
+                updates_with_height
+                    .updates
+                    .iter()
+                    .rev()
+                    .find_map(|update| match update {
+                        BlockchainUpdate::Block(b) => b.time_stamp,
+                        BlockchainUpdate::Microblock(_) | BlockchainUpdate::Rollback(_) => None,
+                    });
+
+            summary.add(&updates_with_height.updates);
+
+            let start = Instant::now();
+            let db_timer = DB_TRANSACTION_DURATION.start_timer();
+
+            let counts = repo
+                .transaction(move |ops| {
+                    let batch = notify_channel
+                        .map(|channel| (channel, BatchCommitted::new(&updates_with_height)));
+                    let counts = handle_updates(
+                        updates_with_height,
+                        ops,
+                        chain_id,
+                        profile,
+                        asset_storage_address,
+                        asset_oracle_key_patterns,
+                        tx_allow_list,
+                        CandlesMode::Defer,
+                    )?;
+
+                    if let Some((channel, mut batch)) = batch {
+                        batch.tx_counts = counts.txs.clone();
+                        batch.send(ops, channel)?;
+                    }
+
+                    info!(
+                        "{} updates were saved to database in {:?}. Last height is {}.",
+                        updates_count,
+                        start.elapsed(),
+                        last_height,
+                    );
+
+                    Ok(counts)
+                })
+                .await?;
+
+            db_timer.observe_duration();
+            counts.observe();
+            UPDATES_PER_BATCH.observe(updates_count as f64);
+            LAST_COMMITTED_HEIGHT.set(last_height as i64);
+            if let Some(time_stamp) = last_block_timestamp {
+                let lag = Utc::now().naive_utc() - time_stamp;
+                BLOCK_TIMESTAMP_LAG.set(lag.num_milliseconds() as f64 / 1000.);
+            }
+        }
+    }
+
+    // candles of all batches are deferred until the sync is finished
+    finish_sync(&repo, summary, sync_start, profile, true, notify_channel).await
+}
+
+fn segments(from_height: u32, to_height: u32, segment_size: u32) -> Vec<Segment> {
+    (from_height..=to_height)
+        .step_by(segment_size as usize)
+        .map(|from_height| Segment {
+            from_height,
+            to_height: to_height.min(from_height.saturating_add(segment_size - 1)),
+        })
+        .collect()
+}
+
+/// Streams the whole segment, starting it over with exponential backoff until succeeded
+async fn fetch_segment<T: UpdatesSource + Clone>(
+    updates_src: T,
+    segment: Segment,
+    updates_per_request: usize,
+    max_wait_time: Duration,
+    reconnect_initial_delay: Duration,
+    reconnect_max_delay: Duration,
+) -> Vec<BlockchainUpdatesWithLastHeight> {
+    let mut delay = reconnect_initial_delay;
+    loop {
+        match try_fetch_segment(
+            updates_src.clone(),
+            segment,
+            updates_per_request,
+            max_wait_time,
+        )
+        .await
+        {
+            Ok(batches) => {
+                info!(
+                    "Segment {}..={} fetched",
+                    segment.from_height, segment.to_height
+                );
+                return batches;
+            }
+            Err(e) => {
+                warn!(
+                    "Cannot fetch segment {}..={}, retrying in {:?}: {}",
+                    segment.from_height, segment.to_height, delay, e
+                );
+                tokio::time::sleep(delay.to_std().unwrap()).await;
+                delay = std::cmp::min(delay * 2, reconnect_max_delay);
+            }
+        }
+    }
+}
+
+async fn try_fetch_segment<T: UpdatesSource>(
+    updates_src: T,
+    segment: Segment,
+    updates_per_request: usize,
+    max_wait_time: Duration,
+) -> Result<Vec<BlockchainUpdatesWithLastHeight>, AppError> {
+    let mut rx = updates_src
+        .stream(
+            segment.from_height,
+            Some(segment.to_height),
+            updates_per_request,
+            max_wait_time,
+        )
+        .await?;
+
+    let mut batches = vec![];
+    while let Some(batch) = rx.recv().await {
+        batches.push(batch?);
+    }
+
+    match batches.last() {
+        Some(batch) if batch.last_height >= segment.to_height => Ok(batches),
+        _ => Err(AppError::StreamClosed(format!(
+            "Updates stream was closed before height {}",
+            segment.to_height
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_cover_the_range() {
+        let bounds = |segments: Vec<Segment>| {
+            segments
+                .iter()
+                .map(|s| (s.from_height, s.to_height))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(bounds(segments(1, 10, 4)), vec![(1, 4), (5, 8), (9, 10)]);
+        assert_eq!(bounds(segments(5, 5, 100)), vec![(5, 5)]);
+        assert!(segments(6, 5, 100).is_empty());
+    }
+}
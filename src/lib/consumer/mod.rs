@@ -1,33 +1,63 @@
+pub mod backfill;
 pub mod models;
 pub mod repo;
 pub mod updates;
+pub mod updates_archive;
+pub mod updates_file;
+#[cfg(test)]
+pub mod updates_mock;
+
+#[cfg(test)]
+mod tests;
 
 use anyhow::{Error, Result};
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike, Utc};
 use itertools::Itertools;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::num::NonZeroU32;
 use std::time::Instant;
+use tokio::select;
 use tokio::sync::mpsc::Receiver;
 use waves_protobuf_schemas::waves::{
     data_entry::Value,
-    events::{transaction_metadata::Metadata, StateUpdate, TransactionMetadata},
+    events::{
+        state_update::lease_update::LeaseStatus, transaction_metadata::Metadata, StateUpdate,
+        TransactionMetadata,
+    },
     signed_transaction::Transaction,
     SignedTransaction, Transaction as WavesTx,
 };
 use wavesexchange_log::{debug, info, timer, warn};
 
-use self::models::{asset_tickers::InsertableAssetTicker, block_microblock::BlockMicroblock};
 use self::models::{
-    asset_tickers::{AssetTickerOverride, DeletedAssetTicker},
-    assets::{AssetOrigin, AssetOverride, AssetUpdate, DeletedAsset},
+    aliases::Alias, asset_tickers::InsertableAssetTicker, block_microblock::BlockMicroblock,
+};
+use self::models::{
+    assets::{AssetOrigin, AssetUpdate},
+    assets_metadata::InsertableAssetMetadata,
+    balance_history::InsertableBalanceHistory,
+    data_entries::InsertableDataEntry,
+    ingest_events::{event_types, InsertableIngestEvent},
+    leases::{statuses as lease_statuses, InsertableLease},
+    versioned::{chain_versions, lowest_deleted_uids},
 };
 use self::repo::RepoOperations;
 use crate::error::Error as AppError;
+use crate::metrics::{
+    BLOCK_TIMESTAMP_LAG, CANDLES_CALCULATION_DURATION, CANDLES_DEFERRED, DB_TRANSACTION_DURATION,
+    LAST_COMMITTED_HEIGHT, ROLLBACKS, ROLLBACK_DEPTH, TXS_INGESTED, TXS_SKIPPED, UPDATES_PER_BATCH,
+};
 use crate::models::BaseAssetInfoUpdate;
-use crate::waves::{extract_asset_id, Address};
-use crate::{config::consumer::Config, utils::into_base58};
+use crate::waves::{extract_asset_id, Address, ASSET_ORACLE_DATA_ENTRY_KEY_REGEX};
+use crate::{
+    config::consumer::{
+        AssetMetadataField, AssetOracleKeyPattern, Config, IngestionProfile, TxAllowList,
+    },
+    utils::{into_base58, into_prefixed_base64},
+};
 use crate::{
     consumer::models::{
         txs::convert::{Tx as ConvertedTx, TxUidGenerator},
@@ -38,7 +68,11 @@ use crate::{
 };
 use fragstrings::frag_parse;
 
-static UID_GENERATOR: Mutex<TxUidGenerator> = Mutex::new(TxUidGenerator::new(100000));
+/// Max transactions per height, uids of the height are `height * TX_UID_MULTIPLIER + n`
+const TX_UID_MULTIPLIER: i64 = 100000;
+
+/// Waves target block interval, to estimate how far behind the consumer is
+const BLOCK_INTERVAL_SECS: i64 = 60;
 
 #[derive(Clone, Debug)]
 pub enum BlockchainUpdate {
@@ -76,6 +110,14 @@ pub struct UidHeight {
     pub height: i32,
 }
 
+/// Whether candles are calculated with every batch of exchange transactions
+#[derive(Debug, Clone, Copy)]
+enum CandlesMode {
+    Calculate,
+    /// Left to be rebuilt from the first exchange not covered by minute candles
+    Defer,
+}
+
 #[derive(Debug)]
 enum UpdatesItem {
     Blocks(Vec<BlockMicroblockAppend>),
@@ -89,70 +131,358 @@ pub struct AssetTickerUpdate {
     pub ticker: String,
 }
 
+#[derive(Debug)]
+pub struct AssetMetadataUpdate {
+    pub asset_id: String,
+    pub field: AssetMetadataField,
+    pub value: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct BalanceHistoryUpdate {
+    pub tx_uid: i64,
+    pub address: String,
+    pub asset_id: String,
+    pub amount_before: i64,
+    pub amount_after: i64,
+}
+
+#[derive(Debug)]
+pub struct LeaseStateUpdate {
+    pub lease_id: String,
+    pub status: &'static str,
+    pub origin_transaction_id: String,
+    pub sender: String,
+    pub sender_public_key: String,
+    pub recipient: String,
+    pub amount: i64,
+}
+
+#[derive(Debug)]
+pub struct DataEntryStateUpdate {
+    pub address: String,
+    pub key: String,
+    pub data_type: Option<&'static str>,
+    pub value_integer: Option<i64>,
+    pub value_boolean: Option<bool>,
+    pub value_binary: Option<String>,
+    pub value_string: Option<String>,
+}
+
 #[async_trait::async_trait]
 pub trait UpdatesSource {
+    /// Streams updates from `from_height`; with `to_height` the stream is closed
+    /// once all updates up to that height (inclusive) are sent.
+    ///
+    /// A closed stream is resubscribed, so errors which resubscribing can't fix
+    /// are sent through it instead.
     async fn stream(
         self,
         from_height: u32,
+        to_height: Option<u32>,
         batch_max_size: usize,
         batch_max_time: Duration,
-    ) -> Result<Receiver<BlockchainUpdatesWithLastHeight>, AppError>;
+    ) -> Result<Receiver<Result<BlockchainUpdatesWithLastHeight, AppError>>, AppError>;
+}
+
+/// Why the consumer stopped without an error
+#[derive(Debug)]
+pub enum Stopped {
+    Shutdown,
+    TargetHeightReached(SyncSummary),
+}
+
+/// What was ingested by a bounded sync
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub from_height: u32,
+    pub to_height: u32,
+    pub blocks: usize,
+    pub microblocks: usize,
+    pub rollbacks: usize,
+    pub transactions: usize,
+    pub elapsed: std::time::Duration,
+}
+
+impl std::fmt::Display for SyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "synced heights {}..={} in {:?}: {} blocks, {} microblocks, {} rollbacks, {} transactions",
+            self.from_height,
+            self.to_height,
+            self.elapsed,
+            self.blocks,
+            self.microblocks,
+            self.rollbacks,
+            self.transactions
+        )
+    }
+}
+
+impl SyncSummary {
+    fn add(&mut self, updates: &[BlockchainUpdate]) {
+        for update in updates {
+            match update {
+                BlockchainUpdate::Block(b) => {
+                    self.blocks += 1;
+                    self.transactions += b.txs.len();
+                }
+                BlockchainUpdate::Microblock(mb) => {
+                    self.microblocks += 1;
+                    self.transactions += mb.txs.len();
+                }
+                BlockchainUpdate::Rollback(_) => self.rollbacks += 1,
+            }
+        }
+    }
+}
+
+/// Payload of the `NOTIFY` sent on `notify_channel` once a batch, a rollback before
+/// subscribing or a finished sync is committed
+#[derive(Debug, Serialize)]
+pub struct BatchCommitted {
+    pub height: u32,
+    /// id of the last block or microblock, or of the block rolled back to
+    pub block_id: Option<String>,
+    /// the last update is a microblock
+    pub microblock: bool,
+    /// stored heights were rolled back by the batch
+    pub rollback: bool,
+    /// stored transactions by type name
+    pub tx_counts: BTreeMap<&'static str, usize>,
+}
+
+impl BatchCommitted {
+    fn new(updates_with_height: &BlockchainUpdatesWithLastHeight) -> Self {
+        let last_update = updates_with_height.updates.last();
+        BatchCommitted {
+            height: updates_with_height.last_height,
+            block_id: last_update.map(|u| match u {
+                BlockchainUpdate::Block(b) | BlockchainUpdate::Microblock(b) => b.id.clone(),
+                BlockchainUpdate::Rollback(id) => id.clone(),
+            }),
+            microblock: matches!(last_update, Some(BlockchainUpdate::Microblock(_))),
+            rollback: updates_with_height
+                .updates
+                .iter()
+                .any(|u| matches!(u, BlockchainUpdate::Rollback(_))),
+            tx_counts: BTreeMap::new(),
+        }
+    }
+
+    /// Stored tip after a transaction which only rolls back or squashes blocks,
+    /// either of them leaves a key block last
+    fn tip<R: RepoOperations>(repo: &mut R, rollback: bool) -> Result<Self> {
+        Ok(BatchCommitted {
+            height: repo.get_current_height()? as u32,
+            block_id: repo.get_last_block_id()?,
+            microblock: false,
+            rollback,
+            tx_counts: BTreeMap::new(),
+        })
+    }
+
+    /// Delivered by Postgres only if the transaction is committed
+    fn send<R: RepoOperations>(&self, repo: &mut R, channel: &str) -> Result<()> {
+        repo.notify(channel, &serde_json::to_string(self)?)
+    }
+}
+
+/// Counts of a database transaction, added to the metrics once it is committed
+#[derive(Debug, Default)]
+struct HandledCounts {
+    /// stored transactions by type name
+    txs: BTreeMap<&'static str, usize>,
+    txs_skipped: usize,
+    /// removed heights of every rollback
+    rollback_depths: Vec<i32>,
 }
 
-// TODO: handle shutdown signals -> rollback current transaction
-pub async fn start<T, R>(updates_src: T, repo: R, config: Config) -> Result<()>
+impl HandledCounts {
+    fn observe(&self) {
+        for (type_name, count) in &self.txs {
+            TXS_INGESTED
+                .with_label_values(&[type_name])
+                .inc_by(*count as u64);
+        }
+        TXS_SKIPPED.inc_by(self.txs_skipped as u64);
+        for depth in &self.rollback_depths {
+            ROLLBACKS.inc();
+            ROLLBACK_DEPTH.observe(*depth as f64);
+        }
+    }
+}
+
+/// Consumes updates until the stream fails, `shutdown` resolves
+/// or, in bounded sync mode, `target_height` is committed.
+///
+/// Shutdown is only observed between batches, so the batch being saved is always
+/// committed or rolled back before returning.
+pub async fn start<T, R, S>(updates_src: T, repo: R, config: Config, shutdown: S) -> Result<Stopped>
 where
-    T: UpdatesSource + Send + 'static,
+    T: UpdatesSource + Clone + Send + 'static,
     R: repo::Repo + Clone + Send + 'static,
+    S: Future<Output = ()> + Send,
 {
     let Config {
-        assets_only,
+        profile,
         chain_id,
         max_wait_time,
         starting_height,
+        target_height,
         updates_per_request,
         asset_storage_address,
         start_rollback_depth,
         rollback_step,
+        asset_oracle_key_patterns,
+        reconnect_initial_delay,
+        reconnect_max_delay,
+        resubscribe_rollback_depth,
+        candles_defer_blocks_behind,
+        tx_allow_list,
+        notify_channel,
         ..
     } = config;
 
+    let candles_defer_lag = candles_defer_blocks_behind
+        .map(|blocks| Duration::seconds(BLOCK_INTERVAL_SECS * blocks as i64));
+    // candles could be deferred before a restart, so they are checked once the tip is reached
+    let mut candles_up_to_date = candles_defer_lag.is_none();
+
     let asset_storage_address: Option<&'static str> =
         asset_storage_address.map(|a| &*Box::leak(a.into_boxed_str()));
-    let starting_from_height = {
-        repo.transaction(move |ops| {
-            match ops.get_blocks_rollback_to(start_rollback_depth, rollback_step) {
-                Ok(Some(rollback_blocks)) => {
-                    rollback(ops, &rollback_blocks, assets_only)?;
-                    Ok(rollback_blocks
-                        .last()
-                        .map(|height| height.height as u32 + 1)
-                        .unwrap_or(starting_height))
-                }
-                Ok(None) => Ok(starting_height),
-                Err(e) => Err(e),
-            }
-        })
-        .await?
-    };
+    let asset_oracle_key_patterns: &'static [AssetOracleKeyPattern] =
+        Box::leak(asset_oracle_key_patterns.into_boxed_slice());
+    let tx_allow_list: Option<&'static TxAllowList> =
+        tx_allow_list.map(|l| &*Box::leak(Box::new(l)));
+    let notify_channel: Option<&'static str> =
+        notify_channel.map(|c| &*Box::leak(c.into_boxed_str()));
+    let starting_from_height = rollback_on_start(
+        &repo,
+        starting_height,
+        start_rollback_depth,
+        rollback_step,
+        profile,
+        notify_channel,
+    )
+    .await?;
 
     info!(
         "Start fetching updates from height {} (by {} block(s) back)",
         starting_from_height, start_rollback_depth
     );
 
+    let mut summary = SyncSummary {
+        from_height: starting_from_height,
+        ..Default::default()
+    };
+    let sync_start = Instant::now();
+
+    if let Some(target_height) = target_height {
+        info!("Bounded sync up to height {}", target_height);
+
+        if starting_from_height > target_height {
+            return finish_sync(
+                &repo,
+                summary,
+                sync_start,
+                profile,
+                !candles_up_to_date,
+                notify_channel,
+            )
+            .await;
+        }
+    }
+
+    let mut subscribed_from_height = starting_from_height;
+    let mut backoff = Backoff::new(reconnect_initial_delay, reconnect_max_delay);
     let mut rx = updates_src
-        .stream(starting_from_height, updates_per_request, max_wait_time)
+        .clone()
+        .stream(
+            starting_from_height,
+            target_height,
+            updates_per_request,
+            max_wait_time,
+        )
         .await?;
 
+    tokio::pin!(shutdown);
+
     loop {
         let mut start = Instant::now();
 
-        let updates_with_height = rx.recv().await.ok_or_else(|| {
-            Error::new(AppError::StreamClosed(
-                "GRPC Stream was closed by the server".to_string(),
-            ))
-        })?;
+        let updates_with_height = select! {
+            biased;
+            _ = &mut shutdown => {
+                info!("Shutdown requested, stop fetching updates");
+                // dropping the receiver stops the updates source and closes the GRPC stream
+                drop(rx);
+                return Ok(Stopped::Shutdown);
+            }
+            updates = rx.recv() => updates,
+        };
+
+        let updates_with_height = match updates_with_height {
+            Some(Ok(updates_with_height)) => updates_with_height,
+            Some(Err(e)) => return Err(e.into()),
+            None => {
+                if let Some(target_height) = target_height {
+                    let current_height = repo.transaction(|ops| ops.get_current_height()).await?;
+                    if current_height >= target_height as i32 {
+                        return finish_sync(
+                            &repo,
+                            summary,
+                            sync_start,
+                            profile,
+                            !candles_up_to_date,
+                            notify_channel,
+                        )
+                        .await;
+                    }
+                }
+
+                // a stream may close right after subscribing, so resubscriptions are delayed
+                // until a batch is committed
+                let delay = backoff.next_delay();
+                warn!("GRPC Stream was closed, resubscribing in {}", delay);
+                select! {
+                    biased;
+                    _ = &mut shutdown => {
+                        info!("Shutdown requested while resubscribing");
+                        return Ok(Stopped::Shutdown);
+                    }
+                    _ = tokio::time::sleep(delay.to_std().unwrap()) => (),
+                };
+
+                subscribed_from_height = prepare_resubscribe(
+                    &repo,
+                    subscribed_from_height,
+                    resubscribe_rollback_depth,
+                    rollback_step,
+                    profile,
+                    notify_channel,
+                )
+                .await?;
+
+                rx = select! {
+                    biased;
+                    _ = &mut shutdown => {
+                        info!("Shutdown requested while resubscribing");
+                        return Ok(Stopped::Shutdown);
+                    }
+                    rx = resubscribe(
+                        updates_src.clone(),
+                        subscribed_from_height,
+                        target_height,
+                        updates_per_request,
+                        max_wait_time,
+                        &mut backoff,
+                    ) => rx,
+                };
+                continue;
+            }
+        };
 
         let updates_count = updates_with_height.updates.len();
         info!(
@@ -162,28 +492,259 @@ where
         );
 
         let last_height = updates_with_height.last_height;
+        let last_block_timestamp = updates_with_height
+            .updates
+            .iter()
+            .rev()
+            .find_map(|u| match u {
+                BlockchainUpdate::Block(b) | BlockchainUpdate::Microblock(b) => b.time_stamp,
+                BlockchainUpdate::Rollback(_) => None,
+            });
+
+        summary.add(&updates_with_height.updates);
+
+        let behind = match (candles_defer_lag, last_block_timestamp) {
+            (Some(lag), Some(time_stamp)) => Utc::now().naive_utc() - time_stamp > lag,
+            _ => false,
+        };
+        if behind && candles_up_to_date {
+            info!("Candles are deferred until the consumer catches up");
+        }
+        let rebuild_candles = !behind && !candles_up_to_date;
 
         start = Instant::now();
+        let db_timer = DB_TRANSACTION_DURATION.start_timer();
 
-        repo.transaction(move |ops| {
-            handle_updates(
-                updates_with_height,
-                ops,
-                chain_id,
-                assets_only,
-                asset_storage_address,
-            )?;
+        let counts = repo
+            .transaction(move |ops| {
+                // the rebuilt range starts at the last minute candle, so this batch must not add any
+                let candles = if behind || rebuild_candles {
+                    CandlesMode::Defer
+                } else {
+                    CandlesMode::Calculate
+                };
+                let batch = notify_channel
+                    .map(|channel| (channel, BatchCommitted::new(&updates_with_height)));
+                let counts = handle_updates(
+                    updates_with_height,
+                    ops,
+                    chain_id,
+                    profile,
+                    asset_storage_address,
+                    asset_oracle_key_patterns,
+                    tx_allow_list,
+                    candles,
+                )?;
+
+                if rebuild_candles && profile.candles {
+                    if let Some(since) = ops.get_candles_outdated_since()? {
+                        info!("Rebuilding candles deferred since {}", since);
+                        calculate_candles_since(ops, since)?;
+                    }
+                }
 
-            info!(
-                "{} updates were saved to database in {:?}. Last height is {}.",
-                updates_count,
-                start.elapsed(),
-                last_height,
-            );
+                if let Some((channel, mut batch)) = batch {
+                    batch.tx_counts = counts.txs.clone();
+                    batch.send(ops, channel)?;
+                }
+
+                info!(
+                    "{} updates were saved to database in {:?}. Last height is {}.",
+                    updates_count,
+                    start.elapsed(),
+                    last_height,
+                );
+
+                Ok(counts)
+            })
+            .await?;
+
+        db_timer.observe_duration();
+        counts.observe();
+        UPDATES_PER_BATCH.observe(updates_count as f64);
+        LAST_COMMITTED_HEIGHT.set(last_height as i64);
+        if let Some(time_stamp) = last_block_timestamp {
+            let lag = Utc::now().naive_utc() - time_stamp;
+            BLOCK_TIMESTAMP_LAG.set(lag.num_milliseconds() as f64 / 1000.);
+        }
+        candles_up_to_date = !behind;
+        CANDLES_DEFERRED.set(behind as i64);
+        backoff.reset();
+    }
+}
 
-            Ok(())
+/// Rolls back the last stored heights, which may be incomplete (e.g. have only a part of microblocks)
+/// or belong to another fork after switching the endpoint, and returns the height to resubscribe from.
+async fn prepare_resubscribe<R: repo::Repo>(
+    repo: &R,
+    subscribed_from_height: u32,
+    rollback_depth: NonZeroU32,
+    rollback_step: NonZeroU32,
+    profile: IngestionProfile,
+    notify_channel: Option<&'static str>,
+) -> Result<u32> {
+    let (height, counts) = repo
+        .transaction(move |ops| {
+            let mut counts = HandledCounts::default();
+            let current_height = ops.get_current_height()? as u32;
+
+            // nothing was saved since the last subscription, so there is nothing to roll back
+            if current_height < subscribed_from_height {
+                return Ok((subscribed_from_height, counts));
+            }
+
+            match ops.get_blocks_rollback_to(rollback_depth, rollback_step)? {
+                Some(rollback_blocks) if !rollback_blocks.is_empty() => {
+                    rollback(ops, &rollback_blocks, profile, &mut counts)?;
+                    if let Some(channel) = notify_channel {
+                        BatchCommitted::tip(ops, true)?.send(ops, channel)?;
+                    }
+                    Ok((rollback_blocks.last().unwrap().height as u32 + 1, counts))
+                }
+                _ => Ok((current_height, counts)),
+            }
         })
         .await?;
+    counts.observe();
+
+    Ok(height)
+}
+
+/// Rolls back the last `start_rollback_depth` blocks, which may be on a fork,
+/// and returns the height to fetch updates from.
+async fn rollback_on_start<R: repo::Repo>(
+    repo: &R,
+    starting_height: u32,
+    start_rollback_depth: NonZeroU32,
+    rollback_step: NonZeroU32,
+    profile: IngestionProfile,
+    notify_channel: Option<&'static str>,
+) -> Result<u32> {
+    let (height, counts) = repo
+        .transaction(move |ops| {
+            let mut counts = HandledCounts::default();
+            match ops.get_blocks_rollback_to(start_rollback_depth, rollback_step) {
+                Ok(Some(rollback_blocks)) => {
+                    rollback(ops, &rollback_blocks, profile, &mut counts)?;
+                    if let Some(channel) = notify_channel.filter(|_| !rollback_blocks.is_empty()) {
+                        BatchCommitted::tip(ops, true)?.send(ops, channel)?;
+                    }
+                    let height = rollback_blocks
+                        .last()
+                        .map(|height| height.height as u32 + 1)
+                        .unwrap_or(starting_height);
+                    Ok((height, counts))
+                }
+                Ok(None) => Ok((starting_height, counts)),
+                Err(e) => Err(e),
+            }
+        })
+        .await?;
+    counts.observe();
+
+    Ok(height)
+}
+
+/// Squashes microblocks left at the target height, so the last block is final,
+/// and recalculates its candles along with the deferred ones.
+async fn finish_sync<R: repo::Repo>(
+    repo: &R,
+    mut summary: SyncSummary,
+    sync_start: Instant,
+    profile: IngestionProfile,
+    candles_deferred: bool,
+    notify_channel: Option<&'static str>,
+) -> Result<Stopped> {
+    summary.to_height = repo
+        .transaction(move |ops| {
+            squash_microblocks(ops, profile)?;
+
+            // looked up before the last block adds its candles, which would hide the deferred ones
+            let deferred_candles_since = if candles_deferred && profile.candles {
+                ops.get_candles_outdated_since()?
+            } else {
+                None
+            };
+            let current_height = ops.get_current_height()?;
+            if profile.candles && current_height > 0 {
+                let key_block_uid = ops.get_key_block_uid()?;
+                ops.calculate_candles_since_block_uid(key_block_uid)?;
+                ops.update_pairs(key_block_uid)?;
+            }
+            if let Some(since) = deferred_candles_since {
+                calculate_candles_since(ops, since)?;
+            }
+            if let Some(channel) = notify_channel.filter(|_| current_height > 0) {
+                BatchCommitted::tip(ops, false)?.send(ops, channel)?;
+            }
+
+            Ok(current_height as u32)
+        })
+        .await?;
+    summary.elapsed = sync_start.elapsed();
+
+    info!("Bounded sync finished: {}", summary);
+
+    Ok(Stopped::TargetHeightReached(summary))
+}
+
+/// Exponentially growing delay between resubscriptions
+#[derive(Debug)]
+struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    next_delay: Duration,
+}
+
+impl Backoff {
+    fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Backoff {
+            initial_delay,
+            max_delay,
+            next_delay: Duration::zero(),
+        }
+    }
+
+    /// The first attempt after a reset is not delayed
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next_delay;
+        self.next_delay =
+            std::cmp::min(std::cmp::max(delay * 2, self.initial_delay), self.max_delay);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next_delay = Duration::zero();
+    }
+}
+
+/// Subscribes to updates, retrying with exponential backoff until succeeded.
+async fn resubscribe<T: UpdatesSource>(
+    updates_src: T,
+    from_height: u32,
+    to_height: Option<u32>,
+    updates_per_request: usize,
+    max_wait_time: Duration,
+    backoff: &mut Backoff,
+) -> Receiver<Result<BlockchainUpdatesWithLastHeight, AppError>>
+where
+    T: Clone,
+{
+    loop {
+        info!("Resubscribing to updates from height {}", from_height);
+
+        match updates_src
+            .clone()
+            .stream(from_height, to_height, updates_per_request, max_wait_time)
+            .await
+        {
+            Ok(rx) => return rx,
+            Err(e) => {
+                let delay = backoff.next_delay();
+                warn!("Cannot resubscribe: {}, next attempt in {}", e, delay);
+                tokio::time::sleep(delay.to_std().unwrap()).await;
+            }
+        }
     }
 }
 
@@ -191,9 +752,13 @@ fn handle_updates<R: RepoOperations>(
     updates_with_height: BlockchainUpdatesWithLastHeight,
     repo: &mut R,
     chain_id: u8,
-    assets_only: bool,
+    profile: IngestionProfile,
     asset_storage_address: Option<&str>,
-) -> Result<()> {
+    asset_oracle_key_patterns: &[AssetOracleKeyPattern],
+    tx_allow_list: Option<&TxAllowList>,
+    candles: CandlesMode,
+) -> Result<HandledCounts> {
+    let mut counts = HandledCounts::default();
     updates_with_height
         .updates
         .into_iter()
@@ -231,31 +796,49 @@ fn handle_updates<R: RepoOperations>(
         .into_iter()
         .try_fold((), |_, update_item| match update_item {
             UpdatesItem::Blocks(ba) => {
-                squash_microblocks(repo, assets_only)?;
-                handle_appends(repo, chain_id, ba, assets_only, asset_storage_address)
+                squash_microblocks(repo, profile)?;
+                handle_appends(
+                    repo,
+                    chain_id,
+                    ba,
+                    profile,
+                    asset_storage_address,
+                    asset_oracle_key_patterns,
+                    tx_allow_list,
+                    candles,
+                    &mut counts,
+                )
             }
             UpdatesItem::Microblock(mba) => handle_appends(
                 repo,
                 chain_id,
                 &vec![mba.to_owned()],
-                assets_only,
+                profile,
                 asset_storage_address,
+                asset_oracle_key_patterns,
+                tx_allow_list,
+                candles,
+                &mut counts,
             ),
             UpdatesItem::Rollback(sig) => {
                 let block = repo.get_block_uid_height(sig)?;
-                rollback(repo, &[block], assets_only)
+                rollback(repo, &[block], profile, &mut counts)
             }
         })?;
 
-    Ok(())
+    Ok(counts)
 }
 
 fn handle_appends<R>(
     repo: &mut R,
     chain_id: u8,
     appends: &Vec<BlockMicroblockAppend>,
-    assets_only: bool,
+    profile: IngestionProfile,
     asset_storage_address: Option<&str>,
+    asset_oracle_key_patterns: &[AssetOracleKeyPattern],
+    tx_allow_list: Option<&TxAllowList>,
+    candles: CandlesMode,
+    counts: &mut HandledCounts,
 ) -> Result<()>
 where
     R: RepoOperations,
@@ -272,49 +855,83 @@ where
     )?;
 
     let block_uids_with_appends = block_uids.into_iter().zip(appends).collect_vec();
+    let mut events = vec![];
 
     timer!("blockchain updates handling");
 
-    let base_asset_info_updates_with_block_uids: Vec<(i64, BaseAssetInfoUpdate)> =
-        block_uids_with_appends
-            .iter()
-            .flat_map(|(block_uid, append)| {
-                extract_base_asset_info_updates(chain_id, append)
-                    .into_iter()
-                    .map(|au| (*block_uid, au))
-                    .collect_vec()
-            })
-            .collect();
+    if profile.assets {
+        let base_asset_info_updates_with_block_uids: Vec<(i64, BaseAssetInfoUpdate)> =
+            block_uids_with_appends
+                .iter()
+                .flat_map(|(block_uid, append)| {
+                    extract_base_asset_info_updates(chain_id, append)
+                        .into_iter()
+                        .map(|au| (*block_uid, au))
+                        .collect_vec()
+                })
+                .collect();
 
-    let inserted_uids =
-        handle_base_asset_info_updates(repo, &base_asset_info_updates_with_block_uids)?;
+        if profile.events {
+            events.extend(ingest_events(
+                event_types::ASSET_UPDATES,
+                &block_uids_with_appends,
+                base_asset_info_updates_with_block_uids
+                    .iter()
+                    .map(|(block_uid, au)| (*block_uid, au.id.as_str())),
+            ));
+        }
 
-    let updates_amount = base_asset_info_updates_with_block_uids.len();
+        let inserted_uids =
+            handle_base_asset_info_updates(repo, &base_asset_info_updates_with_block_uids)?;
 
-    if let Some(uids) = inserted_uids {
-        assert_eq!(uids.len(), base_asset_info_updates_with_block_uids.len());
-        let asset_origins = uids
-            .into_iter()
-            .zip(base_asset_info_updates_with_block_uids)
-            .map(|(uid, (_, au))| AssetOrigin {
-                asset_id: au.id,
-                first_asset_update_uid: uid,
-                origin_transaction_id: au.tx_id,
-                issuer: au.issuer,
-                issue_height: au.update_height,
-                issue_time_stamp: au.updated_at.naive_utc(),
-            })
-            .collect_vec();
+        let updates_amount = base_asset_info_updates_with_block_uids.len();
 
-        assert_eq!(asset_origins.len(), updates_amount);
-        repo.insert_asset_origins(&asset_origins)?;
-    }
+        if let Some(uids) = inserted_uids {
+            assert_eq!(uids.len(), base_asset_info_updates_with_block_uids.len());
+            let asset_origins = uids
+                .into_iter()
+                .zip(base_asset_info_updates_with_block_uids)
+                .map(|(uid, (_, au))| AssetOrigin {
+                    asset_id: au.id,
+                    first_asset_update_uid: uid,
+                    origin_transaction_id: au.tx_id,
+                    issuer: au.issuer,
+                    issue_height: au.update_height,
+                    issue_time_stamp: au.updated_at.naive_utc(),
+                })
+                .collect_vec();
+
+            assert_eq!(asset_origins.len(), updates_amount);
+            repo.insert_asset_origins(&asset_origins)?;
+        }
 
-    info!("handled {} assets updates", updates_amount);
+        info!("handled {} assets updates", updates_amount);
+    }
 
-    if !assets_only {
-        handle_txs(repo, &block_uids_with_appends, chain_id)?;
+    if profile.any_txs() {
+        if let Some(height) = appends.iter().map(|append| append.height).max() {
+            repo.create_txs_partitions(height)?;
+        }
+        let stored_tx_ids = handle_txs(
+            repo,
+            &block_uids_with_appends,
+            chain_id,
+            profile,
+            tx_allow_list,
+            candles,
+            counts,
+        )?;
+
+        events.extend(ingest_events(
+            event_types::TRANSACTIONS,
+            &block_uids_with_appends,
+            stored_tx_ids
+                .iter()
+                .map(|(block_uid, id)| (*block_uid, id.as_str())),
+        ));
+    }
 
+    if profile.waves_data {
         let waves_data = appends
             .into_iter()
             .filter_map(|append| {
@@ -330,7 +947,50 @@ where
         }
     }
 
-    if let Some(storage_addr) = asset_storage_address {
+    if profile.leases() {
+        let lease_updates_with_block_uids: Vec<(i64, LeaseStateUpdate)> = block_uids_with_appends
+            .iter()
+            .flat_map(|(block_uid, append)| {
+                append
+                    .txs
+                    .iter()
+                    .flat_map(|tx| extract_lease_updates(chain_id, tx))
+                    .map(|u| (*block_uid, u))
+                    .collect_vec()
+            })
+            .collect();
+
+        handle_lease_updates(repo, &lease_updates_with_block_uids)?;
+
+        info!(
+            "handled {} lease updates",
+            lease_updates_with_block_uids.len()
+        );
+    }
+
+    if profile.data_entries() {
+        let data_entries_updates_with_block_uids: Vec<(i64, DataEntryStateUpdate)> =
+            block_uids_with_appends
+                .iter()
+                .flat_map(|(block_uid, append)| {
+                    append
+                        .txs
+                        .iter()
+                        .flat_map(extract_data_entries_updates)
+                        .map(|u| (*block_uid, u))
+                        .collect_vec()
+                })
+                .collect();
+
+        handle_data_entries_updates(repo, &data_entries_updates_with_block_uids)?;
+
+        info!(
+            "handled {} data entries updates",
+            data_entries_updates_with_block_uids.len()
+        );
+    }
+
+    if let Some(storage_addr) = asset_storage_address.filter(|_| profile.tickers) {
         timer!("handling asset tickers updates");
         let asset_tickers_updates_with_block_uids: Vec<(&i64, AssetTickerUpdate)> =
             block_uids_with_appends
@@ -345,22 +1005,92 @@ where
                 })
                 .collect();
 
+        if profile.events {
+            events.extend(ingest_events(
+                event_types::ASSET_TICKERS,
+                &block_uids_with_appends,
+                asset_tickers_updates_with_block_uids
+                    .iter()
+                    .map(|(block_uid, u)| (**block_uid, u.asset_id.as_str())),
+            ));
+        }
+
         handle_asset_tickers_updates(repo, &asset_tickers_updates_with_block_uids)?;
 
         info!(
             "handled {} asset tickers updates",
             asset_tickers_updates_with_block_uids.len()
         );
+
+        timer!("handling assets metadata updates");
+        let assets_metadata_updates_with_block_uids: Vec<(i64, i32, AssetMetadataUpdate)> =
+            block_uids_with_appends
+                .iter()
+                .flat_map(|(block_uid, append)| {
+                    append
+                        .txs
+                        .iter()
+                        .flat_map(|tx| {
+                            extract_assets_metadata_updates(
+                                tx,
+                                storage_addr,
+                                asset_oracle_key_patterns,
+                            )
+                        })
+                        .map(|u| (*block_uid, append.height, u))
+                        .collect_vec()
+                })
+                .collect();
+
+        handle_assets_metadata_updates(repo, &assets_metadata_updates_with_block_uids)?;
+
+        info!(
+            "handled {} assets metadata updates",
+            assets_metadata_updates_with_block_uids.len()
+        );
+    }
+
+    if !events.is_empty() {
+        // seq follows the blocks order, the sort is stable so kinds keep their order within a block
+        events.sort_by_key(|e| e.block_uid);
+        repo.insert_ingest_events(&events)?;
     }
 
     Ok(())
 }
 
+/// One outbox event per block with the ids changed in it
+fn ingest_events<'a>(
+    event_type: &str,
+    block_uids_with_appends: &[(i64, &BlockMicroblockAppend)],
+    ids: impl IntoIterator<Item = (i64, &'a str)>,
+) -> Vec<InsertableIngestEvent> {
+    let mut ids_by_block_uid = ids.into_iter().into_group_map();
+    block_uids_with_appends
+        .iter()
+        .filter_map(|(block_uid, append)| {
+            ids_by_block_uid
+                .remove(block_uid)
+                .map(|ids| InsertableIngestEvent {
+                    event_type: event_type.to_owned(),
+                    block_uid: *block_uid,
+                    height: append.height,
+                    payload: serde_json::json!({ "ids": ids.into_iter().unique().collect_vec() }),
+                })
+        })
+        .collect()
+}
+
+/// Returns `(block_uid, id)` of the stored transactions when ingest events are on
 fn handle_txs<R: RepoOperations>(
     repo: &mut R,
     block_uid_data: &Vec<(i64, &BlockMicroblockAppend)>,
     chain_id: u8,
-) -> Result<(), Error> {
+    profile: IngestionProfile,
+    tx_allow_list: Option<&TxAllowList>,
+    candles: CandlesMode,
+    counts: &mut HandledCounts,
+) -> Result<Vec<(i64, String)>, Error> {
     let mut txs_1 = vec![];
     let mut txs_2 = vec![];
     let mut txs_3 = vec![];
@@ -386,23 +1116,48 @@ fn handle_txs<R: RepoOperations>(
     info!("handling {} transactions", txs_count);
 
     let mut first_block_with_tx7_uid = None::<i64>;
+    let mut balance_history_updates = vec![];
+    let mut aliases = vec![];
+    let mut stored_tx_ids = vec![];
 
-    let mut ugen = UID_GENERATOR.lock().unwrap();
+    // uids continue the stored ones, so they don't depend on restarts or on how updates are batched
+    let mut ugen = TxUidGenerator::new(TX_UID_MULTIPLIER);
     for &(block_uid, bm) in block_uid_data {
-        ugen.maybe_update_height(bm.height);
+        if !bm.txs.is_empty() && ugen.height() != bm.height {
+            ugen.start_height(bm.height, repo.get_max_tx_uid_at_height(bm.height)?)?;
+        }
 
         for tx in &bm.txs {
-            let tx_uid = ugen.next();
+            let tx_uid = ugen.next()?;
             let result_tx = match ConvertedTx::try_from((
                 &tx.data, &tx.id, bm.height, &tx.meta, tx_uid, block_uid, chain_id,
             )) {
                 Ok(tx) => tx,
                 Err(AppError::InconsistDataError(msg)) => {
                     warn!("Skipping transaction: {} due to missing data", msg);
+                    counts.txs_skipped += 1;
                     continue;
                 }
                 Err(e) => return Err(e.into()),
             };
+            if !profile.tx_type(result_tx.tx_type()) {
+                continue;
+            }
+            if let Some(allow_list) = tx_allow_list {
+                if !allow_list.allows(result_tx.addresses(), result_tx.asset_ids()) {
+                    continue;
+                }
+            }
+            // balance history references the tx by uid, so only stored txs may have rows
+            balance_history_updates.extend(
+                extract_balance_history_updates(tx, tx_uid)
+                    .into_iter()
+                    .map(|u| (block_uid, u)),
+            );
+            *counts.txs.entry(result_tx.type_name()).or_default() += 1;
+            if profile.events {
+                stored_tx_ids.push((block_uid, tx.id.clone()));
+            }
             match result_tx {
                 ConvertedTx::Genesis(t) => txs_1.push(t),
                 ConvertedTx::Payment(t) => txs_2.push(t),
@@ -418,7 +1173,18 @@ fn handle_txs<R: RepoOperations>(
                 }
                 ConvertedTx::Lease(t) => txs_8.push(t),
                 ConvertedTx::LeaseCancel(t) => txs_9.push(t),
-                ConvertedTx::CreateAlias(t) => txs_10.push(t),
+                ConvertedTx::CreateAlias(t) => {
+                    if t.status == "succeeded" {
+                        aliases.push(Alias {
+                            alias: t.alias.clone(),
+                            address: t.sender.clone(),
+                            qualified_alias: format!("alias:{}:{}", chain_id as char, t.alias),
+                            tx_uid: t.uid,
+                            block_uid: t.block_uid,
+                        });
+                    }
+                    txs_10.push(t);
+                }
                 ConvertedTx::MassTransfer(t) => txs_11.push(t),
                 ConvertedTx::DataTransaction(t) => txs_12.push(t),
                 ConvertedTx::SetScript(t) => txs_13.push(t),
@@ -464,13 +1230,50 @@ fn handle_txs<R: RepoOperations>(
 
     info!("{} transactions handled", txs_count);
 
-    if let Some(block_uid) = first_block_with_tx7_uid {
-        timer!("calculating candles");
+    handle_balance_history_updates(repo, &balance_history_updates)?;
 
-        repo.calculate_candles_since_block_uid(block_uid)?;
+    let aliases_count = aliases.len();
+    handle_aliases(repo, aliases)?;
+
+    info!("handled {} new aliases", aliases_count);
+
+    info!(
+        "handled {} balance history updates",
+        balance_history_updates.len()
+    );
+
+    match candles {
+        _ if !profile.candles => (),
+        CandlesMode::Calculate => {
+            timer!("calculating candles");
+            let _candles_timer = CANDLES_CALCULATION_DURATION.start_timer();
+
+            if let Some(block_uid) = first_block_with_tx7_uid {
+                repo.calculate_candles_since_block_uid(block_uid)?;
+            }
+            // key blocks move the 24h window of pairs even without exchanges
+            if let Some(&(block_uid, _)) = block_uid_data.first() {
+                repo.update_pairs(block_uid)?;
+            }
+        }
+        CandlesMode::Defer => (),
     }
 
-    Ok(())
+    Ok(stored_tx_ids)
+}
+
+/// Calculates candles of all exchange transactions since the timestamp, and pairs
+fn calculate_candles_since<R: RepoOperations>(repo: &mut R, since: NaiveDateTime) -> Result<()> {
+    timer!("calculating deferred candles");
+    let _candles_timer = CANDLES_CALCULATION_DURATION.start_timer();
+
+    let since = since
+        .with_second(0)
+        .and_then(|ts| ts.with_nanosecond(0))
+        .unwrap();
+    repo.calculate_minute_candles(since)?;
+    repo.calculate_non_minute_candles(since)?;
+    repo.calculate_pairs()
 }
 
 fn extract_base_asset_info_updates(
@@ -580,6 +1383,118 @@ fn extract_asset_tickers_updates(tx: &Tx, asset_storage_address: &str) -> Vec<As
         .collect_vec()
 }
 
+fn extract_assets_metadata_updates(
+    tx: &Tx,
+    asset_storage_address: &str,
+    key_patterns: &[AssetOracleKeyPattern],
+) -> Vec<AssetMetadataUpdate> {
+    tx.state_update
+        .data_entries
+        .iter()
+        .filter(|data_entry_update| {
+            asset_storage_address == into_base58(&data_entry_update.address)
+        })
+        .filter_map(|data_entry_update| {
+            let de = data_entry_update.data_entry.as_ref()?;
+            let captures = ASSET_ORACLE_DATA_ENTRY_KEY_REGEX.captures(&de.key)?;
+            let pattern = key_patterns.iter().find(|p| p.key == captures[1])?;
+            let value = match de.value.as_ref() {
+                Some(Value::StringValue(value)) => Some(escape_unicode_null(value)),
+                // other value types are not metadata
+                Some(_) => return None,
+                // key was deleted -> drop metadata field
+                None => None,
+            };
+
+            Some(AssetMetadataUpdate {
+                asset_id: captures[2].to_owned(),
+                field: pattern.field,
+                value,
+            })
+        })
+        .collect_vec()
+}
+
+fn extract_balance_history_updates(tx: &Tx, tx_uid: i64) -> Vec<BalanceHistoryUpdate> {
+    tx.state_update
+        .balances
+        .iter()
+        .filter_map(|balance_update| {
+            balance_update
+                .amount_after
+                .as_ref()
+                .map(|amount_after| BalanceHistoryUpdate {
+                    tx_uid,
+                    address: into_base58(&balance_update.address),
+                    asset_id: extract_asset_id(&amount_after.asset_id),
+                    amount_before: balance_update.amount_before,
+                    amount_after: amount_after.amount,
+                })
+        })
+        .collect_vec()
+}
+
+fn extract_lease_updates(chain_id: u8, tx: &Tx) -> Vec<LeaseStateUpdate> {
+    tx.state_update
+        .individual_leases
+        .iter()
+        .map(|lease_update| LeaseStateUpdate {
+            lease_id: into_base58(&lease_update.lease_id),
+            status: if lease_update.status_after == LeaseStatus::Active as i32 {
+                lease_statuses::ACTIVE
+            } else {
+                lease_statuses::CANCELLED
+            },
+            origin_transaction_id: into_base58(&lease_update.origin_transaction_id),
+            sender: Address::from((lease_update.sender.as_slice(), chain_id)).into(),
+            sender_public_key: into_base58(&lease_update.sender),
+            recipient: into_base58(&lease_update.recipient),
+            amount: lease_update.amount,
+        })
+        .collect_vec()
+}
+
+fn extract_data_entries_updates(tx: &Tx) -> Vec<DataEntryStateUpdate> {
+    tx.state_update
+        .data_entries
+        .iter()
+        .filter_map(|data_entry_update| {
+            data_entry_update.data_entry.as_ref().map(|de| {
+                let (data_type, value_integer, value_boolean, value_binary, value_string) =
+                    match de.value.as_ref() {
+                        Some(Value::IntValue(v)) => (Some("integer"), Some(*v), None, None, None),
+                        Some(Value::BoolValue(v)) => (Some("boolean"), None, Some(*v), None, None),
+                        Some(Value::BinaryValue(v)) => (
+                            Some("binary"),
+                            None,
+                            None,
+                            Some(into_prefixed_base64(v)),
+                            None,
+                        ),
+                        Some(Value::StringValue(v)) => (
+                            Some("string"),
+                            None,
+                            None,
+                            None,
+                            Some(escape_unicode_null(v)),
+                        ),
+                        // key was deleted -> keep a version without a value
+                        None => (None, None, None, None, None),
+                    };
+                DataEntryStateUpdate {
+                    address: into_base58(&data_entry_update.address),
+                    key: escape_unicode_null(&de.key),
+                    data_type,
+                    value_integer,
+                    value_boolean,
+                    value_binary,
+                    value_string,
+                }
+            })
+        })
+        .collect_vec()
+}
+
 fn handle_base_asset_info_updates<R: RepoOperations>(
     repo: &mut R,
     updates: &[(i64, BaseAssetInfoUpdate)],
@@ -611,69 +1526,13 @@ fn handle_base_asset_info_updates<R: RepoOperations>(
         })
         .collect_vec();
 
-    let mut assets_grouped: HashMap<AssetUpdate, Vec<AssetUpdate>> = HashMap::new();
-
-    asset_updates.into_iter().for_each(|update| {
-        let group = assets_grouped.entry(update.clone()).or_insert(vec![]);
-        group.push(update);
-    });
-
-    let assets_grouped = assets_grouped.into_iter().collect_vec();
-
-    let assets_grouped_with_uids_superseded_by = assets_grouped
-        .into_iter()
-        .map(|(group_key, group)| {
-            let mut updates = group
-                .into_iter()
-                .sorted_by_key(|item| item.uid)
-                .collect::<Vec<AssetUpdate>>();
-
-            let mut last_uid = std::i64::MAX - 1;
-            (
-                group_key,
-                updates
-                    .as_mut_slice()
-                    .iter_mut()
-                    .rev()
-                    .map(|cur| {
-                        cur.superseded_by = last_uid;
-                        last_uid = cur.uid;
-                        cur.to_owned()
-                    })
-                    .sorted_by_key(|item| item.uid)
-                    .collect(),
-            )
-        })
-        .collect::<Vec<(AssetUpdate, Vec<AssetUpdate>)>>();
-
-    let assets_first_uids: Vec<AssetOverride> = assets_grouped_with_uids_superseded_by
-        .iter()
-        .map(|(_, group)| {
-            let first = group.iter().next().unwrap().clone();
-            AssetOverride {
-                superseded_by: first.uid,
-                id: first.asset_id,
-            }
-        })
-        .collect();
-
-    repo.close_assets_superseded_by(&assets_first_uids)?;
+    let (asset_updates, overrides) = chain_versions(asset_updates);
 
-    let assets_with_uids_superseded_by = &assets_grouped_with_uids_superseded_by
-        .into_iter()
-        .flat_map(|(_, v)| v)
-        .sorted_by_key(|asset| asset.uid)
-        .collect_vec();
-
-    repo.insert_asset_updates(assets_with_uids_superseded_by)?;
+    repo.close_assets_superseded_by(&overrides)?;
+    repo.insert_asset_updates(&asset_updates)?;
     repo.set_assets_next_update_uid(assets_next_uid + updates_count as i64)?;
 
-    Ok(Some(
-        assets_with_uids_superseded_by
-            .into_iter()
-            .map(|a| a.uid)
-            .collect_vec(),
-    ))
+    Ok(Some(asset_updates.into_iter().map(|a| a.uid).collect_vec()))
 }
 
 fn handle_asset_tickers_updates<R: RepoOperations>(
@@ -702,71 +1561,235 @@ fn handle_asset_tickers_updates<R: RepoOperations>(
         )
         .collect_vec();
 
-    let mut asset_tickers_grouped: HashMap<InsertableAssetTicker, Vec<InsertableAssetTicker>> =
-        HashMap::new();
+    let (asset_tickers_updates, overrides) = chain_versions(asset_tickers_updates);
+
+    repo.close_asset_tickers_superseded_by(&overrides)?;
+    repo.insert_asset_tickers(&asset_tickers_updates)?;
 
-    asset_tickers_updates.into_iter().for_each(|update| {
-        let group = asset_tickers_grouped
-            .entry(update.clone())
-            .or_insert(vec![]);
-        group.push(update);
-    });
+    repo.set_asset_tickers_next_update_uid(asset_tickers_next_uid + updates_count as i64)
+}
 
-    let asset_tickers_grouped = asset_tickers_grouped.into_iter().collect_vec();
+fn handle_assets_metadata_updates<R: RepoOperations>(
+    repo: &mut R,
+    updates: &[(i64, i32, AssetMetadataUpdate)],
+) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
 
-    let asset_tickers_grouped_with_uids_superseded_by = asset_tickers_grouped
+    let updates_count = updates.len();
+
+    let assets_metadata_next_uid = repo.get_next_assets_metadata_uid()?;
+
+    let asset_ids = updates
+        .iter()
+        .map(|(_, _, u)| u.asset_id.clone())
+        .unique()
+        .collect_vec();
+
+    // every update changes a single column, so each new version starts from the latest one
+    let mut latest_metadata: HashMap<String, InsertableAssetMetadata> = repo
+        .get_current_assets_metadata(&asset_ids)?
         .into_iter()
-        .map(|(group_key, group)| {
-            let mut updates = group
-                .into_iter()
-                .sorted_by_key(|item| item.uid)
-                .collect::<Vec<InsertableAssetTicker>>();
-
-            let mut last_uid = std::i64::MAX - 1;
-            (
-                group_key,
-                updates
-                    .as_mut_slice()
-                    .iter_mut()
-                    .rev()
-                    .map(|cur| {
-                        cur.superseded_by = last_uid;
-                        last_uid = cur.uid;
-                        cur.to_owned()
-                    })
-                    .sorted_by_key(|item| item.uid)
-                    .collect(),
-            )
-        })
-        .collect::<Vec<(InsertableAssetTicker, Vec<InsertableAssetTicker>)>>();
+        .map(|m| (m.asset_id.clone(), m))
+        .collect();
 
-    let asset_tickers_first_uids: Vec<AssetTickerOverride> =
-        asset_tickers_grouped_with_uids_superseded_by
-            .iter()
-            .map(|(_, group)| {
-                let first = group.iter().next().unwrap().clone();
-                AssetTickerOverride {
-                    superseded_by: first.uid,
-                    asset_id: first.asset_id,
+    let assets_metadata_updates = updates
+        .iter()
+        .enumerate()
+        .map(|(update_idx, (block_uid, height, metadata_update))| {
+            let mut metadata = latest_metadata
+                .get(&metadata_update.asset_id)
+                .cloned()
+                .unwrap_or_else(|| InsertableAssetMetadata {
+                    uid: -1,
+                    superseded_by: -1,
+                    block_uid: -1,
+                    asset_id: metadata_update.asset_id.clone(),
+                    asset_name: None,
+                    ticker: None,
+                    height: None,
+                });
+
+            metadata.uid = assets_metadata_next_uid + update_idx as i64;
+            metadata.superseded_by = -1;
+            metadata.block_uid = *block_uid;
+            metadata.height = Some(*height);
+            match metadata_update.field {
+                AssetMetadataField::AssetName => {
+                    metadata.asset_name = metadata_update.value.clone()
                 }
-            })
-            .collect();
+                AssetMetadataField::Ticker => metadata.ticker = metadata_update.value.clone(),
+            }
 
-    repo.close_asset_tickers_superseded_by(&asset_tickers_first_uids)?;
+            latest_metadata.insert(metadata.asset_id.clone(), metadata.clone());
+            metadata
+        })
+        .collect_vec();
 
-    let asset_tickers_with_uids_superseded_by = &asset_tickers_grouped_with_uids_superseded_by
-        .clone()
-        .into_iter()
-        .flat_map(|(_, v)| v)
-        .sorted_by_key(|asset_tickers| asset_tickers.uid)
+    let (assets_metadata_updates, overrides) = chain_versions(assets_metadata_updates);
+
+    repo.close_assets_metadata_superseded_by(&overrides)?;
+    repo.insert_assets_metadata(&assets_metadata_updates)?;
+
+    repo.set_assets_metadata_next_update_uid(assets_metadata_next_uid + updates_count as i64)
+}
+
+fn handle_balance_history_updates<R: RepoOperations>(
+    repo: &mut R,
+    updates: &[(i64, BalanceHistoryUpdate)],
+) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let updates_count = updates.len();
+
+    let balance_history_next_uid = repo.get_next_balance_history_uid()?;
+
+    let balance_history_updates = updates
+        .iter()
+        .enumerate()
+        .map(
+            |(update_idx, (block_uid, balance_update))| InsertableBalanceHistory {
+                uid: balance_history_next_uid + update_idx as i64,
+                superseded_by: -1,
+                block_uid: *block_uid,
+                tx_uid: balance_update.tx_uid,
+                address: balance_update.address.clone(),
+                asset_id: balance_update.asset_id.clone(),
+                amount_before: balance_update.amount_before,
+                amount_after: balance_update.amount_after,
+            },
+        )
         .collect_vec();
 
-    repo.insert_asset_tickers(asset_tickers_with_uids_superseded_by)?;
+    let (balance_history_updates, overrides) = chain_versions(balance_history_updates);
 
-    repo.set_asset_tickers_next_update_uid(asset_tickers_next_uid + updates_count as i64)
+    repo.close_balance_history_superseded_by(&overrides)?;
+    repo.insert_balance_history(&balance_history_updates)?;
+
+    repo.set_balance_history_next_update_uid(balance_history_next_uid + updates_count as i64)
 }
 
-fn squash_microblocks<R: RepoOperations>(repo: &mut R, assets_only: bool) -> Result<()> {
+fn handle_lease_updates<R: RepoOperations>(
+    repo: &mut R,
+    updates: &[(i64, LeaseStateUpdate)],
+) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let updates_count = updates.len();
+
+    let leases_next_uid = repo.get_next_leases_uid()?;
+
+    let lease_updates = updates
+        .iter()
+        .enumerate()
+        .map(|(update_idx, (block_uid, lease_update))| InsertableLease {
+            uid: leases_next_uid + update_idx as i64,
+            superseded_by: -1,
+            block_uid: *block_uid,
+            lease_id: lease_update.lease_id.clone(),
+            status: lease_update.status.to_string(),
+            origin_transaction_id: lease_update.origin_transaction_id.clone(),
+            sender: lease_update.sender.clone(),
+            sender_public_key: lease_update.sender_public_key.clone(),
+            recipient: lease_update.recipient.clone(),
+            amount: lease_update.amount,
+        })
+        .collect_vec();
+
+    let (lease_updates, overrides) = chain_versions(lease_updates);
+
+    repo.close_leases_superseded_by(&overrides)?;
+    repo.insert_leases(&lease_updates)?;
+
+    repo.set_leases_next_update_uid(leases_next_uid + updates_count as i64)
+}
+
+fn handle_data_entries_updates<R: RepoOperations>(
+    repo: &mut R,
+    updates: &[(i64, DataEntryStateUpdate)],
+) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let updates_count = updates.len();
+
+    let data_entries_next_uid = repo.get_next_data_entries_uid()?;
+
+    let data_entries_updates = updates
+        .iter()
+        .enumerate()
+        .map(
+            |(update_idx, (block_uid, entry_update))| InsertableDataEntry {
+                uid: data_entries_next_uid + update_idx as i64,
+                superseded_by: -1,
+                block_uid: *block_uid,
+                address: entry_update.address.clone(),
+                key: entry_update.key.clone(),
+                data_type: entry_update.data_type.map(String::from),
+                value_integer: entry_update.value_integer,
+                value_boolean: entry_update.value_boolean,
+                value_binary: entry_update.value_binary.clone(),
+                value_string: entry_update.value_string.clone(),
+            },
+        )
+        .collect_vec();
+
+    let (data_entries_updates, overrides) = chain_versions(data_entries_updates);
+
+    repo.close_data_entries_superseded_by(&overrides)?;
+    repo.insert_data_entries(&data_entries_updates)?;
+
+    repo.set_data_entries_next_update_uid(data_entries_next_uid + updates_count as i64)
+}
+
+fn handle_aliases<R: RepoOperations>(repo: &mut R, aliases: Vec<Alias>) -> Result<()> {
+    if aliases.is_empty() {
+        return Ok(());
+    }
+
+    // the node rejects a second creation, so the first one wins over inconsistent data
+    let mut new_aliases: HashMap<String, Alias> = HashMap::new();
+
+    for alias in aliases {
+        if let Some(prev) = new_aliases.get(&alias.alias) {
+            warn!(
+                "alias {} is created twice: by tx_uid {} for {} and by tx_uid {} for {}, skipping the latter",
+                alias.alias, prev.tx_uid, prev.address, alias.tx_uid, alias.address
+            );
+            continue;
+        }
+        new_aliases.insert(alias.alias.clone(), alias);
+    }
+
+    let existing_aliases = repo.get_aliases(&new_aliases.keys().cloned().collect_vec())?;
+
+    // stored aliases are authoritative, the same creation may just be registered already
+    for existing in existing_aliases {
+        if let Some(new) = new_aliases.remove(&existing.alias) {
+            if existing.tx_uid != new.tx_uid {
+                warn!(
+                    "alias {} is already owned by {} since tx_uid {}, skipping its registration for {} by tx_uid {}",
+                    existing.alias, existing.address, existing.tx_uid, new.address, new.tx_uid
+                );
+            }
+        }
+    }
+
+    let new_aliases = new_aliases
+        .into_values()
+        .sorted_by_key(|alias| alias.tx_uid)
+        .collect_vec();
+
+    repo.insert_aliases(&new_aliases)
+}
+
+fn squash_microblocks<R: RepoOperations>(repo: &mut R, profile: IngestionProfile) -> Result<()> {
     let last_microblock_id = repo.get_total_block_id()?;
 
     if let Some(lmid) = last_microblock_id {
@@ -779,9 +1802,21 @@ fn squash_microblocks<R: RepoOperations>(repo: &mut R, assets_only: bool) -> Res
 
         repo.update_assets_block_references(last_block_uid)?;
         repo.update_asset_tickers_block_references(last_block_uid)?;
-
-        if !assets_only {
-            repo.update_transactions_references(last_block_uid)?;
+        repo.update_assets_metadata_block_references(last_block_uid)?;
+        repo.update_transactions_references(last_block_uid)?;
+        repo.update_balance_history_block_references(last_block_uid)?;
+        repo.update_leases_block_references(last_block_uid)?;
+        repo.update_data_entries_block_references(last_block_uid)?;
+        repo.update_aliases_block_references(last_block_uid)?;
+
+        if profile.events {
+            let block_uids = repo.get_block_uids_after(last_block_uid)?;
+            repo.insert_ingest_events(&vec![InsertableIngestEvent {
+                event_type: event_types::MICROBLOCKS_SQUASHED.to_owned(),
+                block_uid: last_block_uid,
+                height: repo.get_current_height()?,
+                payload: serde_json::json!({ "block_uids": block_uids }),
+            }])?;
         }
 
         repo.delete_microblocks()?;
@@ -791,10 +1826,11 @@ fn squash_microblocks<R: RepoOperations>(repo: &mut R, assets_only: bool) -> Res
     Ok(())
 }
 
-pub fn rollback<R: RepoOperations>(
+fn rollback<R: RepoOperations>(
     repo: &mut R,
     blocks: &[UidHeight],
-    assets_only: bool,
+    profile: IngestionProfile,
+    counts: &mut HandledCounts,
 ) -> Result<()> {
     if let Some(b) = blocks.last() {
         debug!(
@@ -802,21 +1838,49 @@ pub fn rollback<R: RepoOperations>(
             b.uid, b.height
         );
     }
+    let mut removed_height = None;
 
     for &block in blocks {
         let UidHeight { uid, height } = block;
 
         debug!("rolling back to block_uid = {}, height = {}", uid, height);
 
+        if profile.events {
+            let block_uids = repo.get_block_uids_after(uid)?;
+            if !block_uids.is_empty() {
+                repo.insert_ingest_events(&vec![InsertableIngestEvent {
+                    event_type: event_types::ROLLBACK.to_owned(),
+                    block_uid: uid,
+                    height,
+                    payload: serde_json::json!({ "block_uids": block_uids }),
+                }])?;
+            }
+        }
+
         rollback_assets(repo, uid)?;
         rollback_asset_tickers(repo, uid)?;
-
-        if !assets_only {
-            repo.rollback_transactions(uid)?;
+        rollback_assets_metadata(repo, uid)?;
+        rollback_balance_history(repo, uid)?;
+        rollback_leases(repo, uid)?;
+        rollback_data_entries(repo, uid)?;
+        repo.rollback_aliases(uid)?;
+        repo.rollback_transactions(uid)?;
+        if profile.candles {
             rollback_candles(repo, uid)?;
         }
 
-        repo.rollback_blocks_microblocks(uid)?;
+        removed_height = removed_height.max(repo.rollback_blocks_microblocks(uid)?);
+        if profile.candles {
+            // the pairs window ends at the block with its recalculated candles once later ones
+            // are gone
+            repo.update_pairs(uid)?;
+        }
+    }
+
+    if let Some(b) = blocks.last() {
+        counts
+            .rollback_depths
+            .push(removed_height.map_or(0, |height| height - b.height));
     }
     Ok(())
 }
@@ -824,40 +1888,42 @@ pub fn rollback<R: RepoOperations>(
 fn rollback_assets<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
     let deleted = repo.rollback_assets(block_uid)?;
 
-    let mut grouped_deleted: HashMap<DeletedAsset, Vec<DeletedAsset>> = HashMap::new();
+    repo.reopen_assets_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
+}
 
-    deleted.into_iter().for_each(|item| {
-        let group = grouped_deleted.entry(item.clone()).or_insert(vec![]);
-        group.push(item);
-    });
+fn rollback_asset_tickers<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
+    let deleted = repo.rollback_asset_tickers(&block_uid)?;
 
-    let lowest_deleted_uids: Vec<i64> = grouped_deleted
-        .into_iter()
-        .filter_map(|(_, group)| group.into_iter().min_by_key(|i| i.uid).map(|i| i.uid))
-        .collect();
+    repo.reopen_asset_tickers_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
+}
 
-    repo.reopen_assets_superseded_by(&lowest_deleted_uids)
+fn rollback_assets_metadata<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
+    let deleted = repo.rollback_assets_metadata(block_uid)?;
+
+    repo.reopen_assets_metadata_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
 }
 
-fn rollback_asset_tickers<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
-    let deleted = repo.rollback_asset_tickers(&block_uid)?;
+fn rollback_balance_history<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
+    let deleted = repo.rollback_balance_history(block_uid)?;
 
-    let mut grouped_deleted: HashMap<DeletedAssetTicker, Vec<DeletedAssetTicker>> = HashMap::new();
+    repo.reopen_balance_history_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
+}
 
-    deleted.into_iter().for_each(|item| {
-        let group = grouped_deleted.entry(item.clone()).or_insert(vec![]);
-        group.push(item);
-    });
+fn rollback_leases<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
+    let deleted = repo.rollback_leases(block_uid)?;
 
-    let lowest_deleted_uids: Vec<i64> = grouped_deleted
-        .into_iter()
-        .filter_map(|(_, group)| group.into_iter().min_by_key(|i| i.uid).map(|i| i.uid))
-        .collect();
+    repo.reopen_leases_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
+}
+
+fn rollback_data_entries<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
+    let deleted = repo.rollback_data_entries(block_uid)?;
 
-    repo.reopen_asset_tickers_superseded_by(&lowest_deleted_uids)
+    repo.reopen_data_entries_superseded_by(&lowest_deleted_uids(deleted, |d| d.uid))
 }
 
 fn rollback_candles<R: RepoOperations>(repo: &mut R, block_uid: i64) -> Result<()> {
     repo.rollback_candles(block_uid)?;
+
+    let _candles_timer = CANDLES_CALCULATION_DURATION.start_timer();
     repo.calculate_candles_since_block_uid(block_uid)
 }
@@ -0,0 +1,12 @@
+use crate::schema::aliases;
+use diesel::{Insertable, Queryable};
+
+#[derive(Clone, Debug, Insertable, Queryable)]
+#[diesel(table_name = aliases)]
+pub struct Alias {
+    pub alias: String,
+    pub address: String,
+    pub qualified_alias: String,
+    pub tx_uid: i64,
+    pub block_uid: i64,
+}
@@ -7,6 +7,8 @@ pub type BlockUid = i64;
 pub type UpdateUid = i64;
 
 #[derive(Clone, Debug, Insertable, Queryable)]
+// for COPY; the optional columns have no defaults, so INSERT writes the same NULLs
+#[diesel(treat_none_as_default_value = false)]
 pub struct AssetUpdate {
     pub block_uid: i64,
     pub uid: i64,
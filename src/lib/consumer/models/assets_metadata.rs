@@ -0,0 +1,56 @@
+use std::hash::{Hash, Hasher};
+
+use crate::schema::assets_metadata;
+use diesel::{Insertable, Queryable};
+
+#[derive(Clone, Debug, Insertable, Queryable)]
+#[diesel(table_name = assets_metadata)]
+pub struct InsertableAssetMetadata {
+    pub uid: i64,
+    pub superseded_by: i64,
+    pub block_uid: i64,
+    pub asset_id: String,
+    pub asset_name: Option<String>,
+    pub ticker: Option<String>,
+    pub height: Option<i32>,
+}
+
+impl PartialEq for InsertableAssetMetadata {
+    fn eq(&self, other: &InsertableAssetMetadata) -> bool {
+        (&self.asset_id) == (&other.asset_id)
+    }
+}
+
+impl Eq for InsertableAssetMetadata {}
+
+impl Hash for InsertableAssetMetadata {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.asset_id.hash(state);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetMetadataOverride {
+    pub superseded_by: i64,
+    pub asset_id: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeletedAssetMetadata {
+    pub uid: i64,
+    pub asset_id: String,
+}
+
+impl PartialEq for DeletedAssetMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.asset_id) == (&other.asset_id)
+    }
+}
+
+impl Eq for DeletedAssetMetadata {}
+
+impl Hash for DeletedAssetMetadata {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.asset_id.hash(state);
+    }
+}
@@ -0,0 +1,61 @@
+use std::hash::{Hash, Hasher};
+
+use crate::schema::balance_history;
+use diesel::Insertable;
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = balance_history)]
+pub struct InsertableBalanceHistory {
+    pub uid: i64,
+    pub superseded_by: i64,
+    pub block_uid: i64,
+    pub tx_uid: i64,
+    pub address: String,
+    pub asset_id: String,
+    pub amount_before: i64,
+    pub amount_after: i64,
+}
+
+impl PartialEq for InsertableBalanceHistory {
+    fn eq(&self, other: &InsertableBalanceHistory) -> bool {
+        (&self.address, &self.asset_id) == (&other.address, &other.asset_id)
+    }
+}
+
+impl Eq for InsertableBalanceHistory {}
+
+impl Hash for InsertableBalanceHistory {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+        self.asset_id.hash(state);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BalanceHistoryOverride {
+    pub superseded_by: i64,
+    pub address: String,
+    pub asset_id: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeletedBalanceHistory {
+    pub uid: i64,
+    pub address: String,
+    pub asset_id: String,
+}
+
+impl PartialEq for DeletedBalanceHistory {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.address, &self.asset_id) == (&other.address, &other.asset_id)
+    }
+}
+
+impl Eq for DeletedBalanceHistory {}
+
+impl Hash for DeletedBalanceHistory {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+        self.asset_id.hash(state);
+    }
+}
@@ -0,0 +1,63 @@
+use std::hash::{Hash, Hasher};
+
+use crate::schema::data_entries;
+use diesel::Insertable;
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = data_entries)]
+pub struct InsertableDataEntry {
+    pub uid: i64,
+    pub superseded_by: i64,
+    pub block_uid: i64,
+    pub address: String,
+    pub key: String,
+    pub data_type: Option<String>,
+    pub value_integer: Option<i64>,
+    pub value_boolean: Option<bool>,
+    pub value_binary: Option<String>,
+    pub value_string: Option<String>,
+}
+
+impl PartialEq for InsertableDataEntry {
+    fn eq(&self, other: &InsertableDataEntry) -> bool {
+        (&self.address, &self.key) == (&other.address, &other.key)
+    }
+}
+
+impl Eq for InsertableDataEntry {}
+
+impl Hash for InsertableDataEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+        self.key.hash(state);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DataEntryOverride {
+    pub superseded_by: i64,
+    pub address: String,
+    pub key: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeletedDataEntry {
+    pub uid: i64,
+    pub address: String,
+    pub key: String,
+}
+
+impl PartialEq for DeletedDataEntry {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.address, &self.key) == (&other.address, &other.key)
+    }
+}
+
+impl Eq for DeletedDataEntry {}
+
+impl Hash for DeletedDataEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+        self.key.hash(state);
+    }
+}
@@ -0,0 +1,27 @@
+use crate::schema::ingest_events;
+use diesel::Insertable;
+use serde_json::Value;
+
+pub mod event_types {
+    /// payload: `{"ids": [<tx id>]}`
+    pub const TRANSACTIONS: &str = "transactions";
+    /// payload: `{"ids": [<asset id>]}`
+    pub const ASSET_UPDATES: &str = "asset_updates";
+    /// payload: `{"ids": [<asset id>]}`
+    pub const ASSET_TICKERS: &str = "asset_tickers";
+    /// `block_uid` is the block rolled back to, payload: `{"block_uids": [<removed block uid>]}`
+    pub const ROLLBACK: &str = "rollback";
+    /// changes of the microblocks now belong to the key block `block_uid`,
+    /// payload: `{"block_uids": [<microblock uid>]}`
+    pub const MICROBLOCKS_SQUASHED: &str = "microblocks_squashed";
+}
+
+/// `seq` and `created_at` are assigned by the database
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = ingest_events)]
+pub struct InsertableIngestEvent {
+    pub event_type: String,
+    pub block_uid: i64,
+    pub height: i32,
+    pub payload: Value,
+}
@@ -0,0 +1,64 @@
+use std::hash::{Hash, Hasher};
+
+use crate::schema::leases;
+use diesel::Insertable;
+
+pub mod statuses {
+    pub const ACTIVE: &str = "active";
+    pub const CANCELLED: &str = "cancelled";
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = leases)]
+pub struct InsertableLease {
+    pub uid: i64,
+    pub superseded_by: i64,
+    pub block_uid: i64,
+    pub lease_id: String,
+    pub status: String,
+    pub origin_transaction_id: String,
+    pub sender: String,
+    pub sender_public_key: String,
+    pub recipient: String,
+    pub amount: i64,
+}
+
+impl PartialEq for InsertableLease {
+    fn eq(&self, other: &InsertableLease) -> bool {
+        (&self.lease_id) == (&other.lease_id)
+    }
+}
+
+impl Eq for InsertableLease {}
+
+impl Hash for InsertableLease {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lease_id.hash(state);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaseOverride {
+    pub superseded_by: i64,
+    pub lease_id: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeletedLease {
+    pub uid: i64,
+    pub lease_id: String,
+}
+
+impl PartialEq for DeletedLease {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.lease_id) == (&other.lease_id)
+    }
+}
+
+impl Eq for DeletedLease {}
+
+impl Hash for DeletedLease {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lease_id.hash(state);
+    }
+}
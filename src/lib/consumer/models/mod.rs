@@ -1,6 +1,13 @@
+pub mod aliases;
 pub mod asset_tickers;
 pub mod assets;
+pub mod assets_metadata;
+pub mod balance_history;
 pub mod block_microblock;
 pub mod candles;
+pub mod data_entries;
+pub mod ingest_events;
+pub mod leases;
 pub mod txs;
+pub mod versioned;
 pub mod waves_data;
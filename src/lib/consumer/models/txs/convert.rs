@@ -42,32 +42,173 @@ pub enum Tx {
     Ethereum(Tx18Combined),
 }
 
+impl Tx {
+    pub fn tx_type(&self) -> i16 {
+        match self {
+            Tx::Genesis(_) => 1,
+            Tx::Payment(_) => 2,
+            Tx::Issue(_) => 3,
+            Tx::Transfer(_) => 4,
+            Tx::Reissue(_) => 5,
+            Tx::Burn(_) => 6,
+            Tx::Exchange(_) => 7,
+            Tx::Lease(_) => 8,
+            Tx::LeaseCancel(_) => 9,
+            Tx::CreateAlias(_) => 10,
+            Tx::MassTransfer(_) => 11,
+            Tx::DataTransaction(_) => 12,
+            Tx::SetScript(_) => 13,
+            Tx::SponsorFee(_) => 14,
+            Tx::SetAssetScript(_) => 15,
+            Tx::InvokeScript(_) => 16,
+            Tx::UpdateAssetInfo(_) => 17,
+            Tx::Ethereum(_) => 18,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Tx::Genesis(_) => "genesis",
+            Tx::Payment(_) => "payment",
+            Tx::Issue(_) => "issue",
+            Tx::Transfer(_) => "transfer",
+            Tx::Reissue(_) => "reissue",
+            Tx::Burn(_) => "burn",
+            Tx::Exchange(_) => "exchange",
+            Tx::Lease(_) => "lease",
+            Tx::LeaseCancel(_) => "lease_cancel",
+            Tx::CreateAlias(_) => "create_alias",
+            Tx::MassTransfer(_) => "mass_transfer",
+            Tx::DataTransaction(_) => "data",
+            Tx::SetScript(_) => "set_script",
+            Tx::SponsorFee(_) => "sponsor_fee",
+            Tx::SetAssetScript(_) => "set_asset_script",
+            Tx::InvokeScript(_) => "invoke_script",
+            Tx::UpdateAssetInfo(_) => "update_asset_info",
+            Tx::Ethereum(_) => "ethereum",
+        }
+    }
+
+    /// Sender, recipients, dApp and order senders
+    pub fn addresses(&self) -> Vec<&str> {
+        match self {
+            Tx::Genesis(t) => [t.sender.as_deref(), Some(t.recipient_address.as_str())]
+                .into_iter()
+                .flatten()
+                .collect(),
+            Tx::Payment(t) => vec![t.sender.as_str(), t.recipient_address.as_str()],
+            Tx::Issue(t) => vec![t.sender.as_str()],
+            Tx::Transfer(t) => vec![t.sender.as_str(), t.recipient_address.as_str()],
+            Tx::Reissue(t) => vec![t.sender.as_str()],
+            Tx::Burn(t) => vec![t.sender.as_str()],
+            Tx::Exchange(t) => {
+                fn order_sender(order: &Value) -> Option<&str> {
+                    order.get("sender").and_then(Value::as_str)
+                }
+                [
+                    Some(t.sender.as_str()),
+                    order_sender(&t.order1),
+                    order_sender(&t.order2),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            }
+            Tx::Lease(t) => vec![t.sender.as_str(), t.recipient_address.as_str()],
+            Tx::LeaseCancel(t) => vec![t.sender.as_str()],
+            Tx::CreateAlias(t) => vec![t.sender.as_str()],
+            Tx::MassTransfer(t) => std::iter::once(t.tx.sender.as_str())
+                .chain(t.transfers.iter().map(|tr| tr.recipient_address.as_str()))
+                .collect(),
+            Tx::DataTransaction(t) => vec![t.tx.sender.as_str()],
+            Tx::SetScript(t) => vec![t.sender.as_str()],
+            Tx::SponsorFee(t) => vec![t.sender.as_str()],
+            Tx::SetAssetScript(t) => vec![t.sender.as_str()],
+            Tx::InvokeScript(t) => vec![t.tx.sender.as_str(), t.tx.dapp_address.as_str()],
+            Tx::UpdateAssetInfo(t) => vec![t.sender.as_str()],
+            Tx::Ethereum(t) => vec![t.tx.sender.as_str(), t.recipient_address.as_str()],
+        }
+    }
+
+    /// Issued, transferred, traded, fee and payment assets
+    pub fn asset_ids(&self) -> Vec<&str> {
+        match self {
+            Tx::Genesis(_) | Tx::Payment(_) => vec![],
+            Tx::Issue(t) => vec![t.asset_id.as_str()],
+            Tx::Transfer(t) => vec![t.asset_id.as_str(), t.fee_asset_id.as_str()],
+            Tx::Reissue(t) => vec![t.asset_id.as_str()],
+            Tx::Burn(t) => vec![t.asset_id.as_str()],
+            Tx::Exchange(t) => vec![
+                t.amount_asset_id.as_str(),
+                t.price_asset_id.as_str(),
+                t.fee_asset_id.as_str(),
+            ],
+            Tx::Lease(_) | Tx::LeaseCancel(_) | Tx::CreateAlias(_) => vec![],
+            Tx::MassTransfer(t) => vec![t.tx.asset_id.as_str()],
+            Tx::DataTransaction(_) | Tx::SetScript(_) => vec![],
+            Tx::SponsorFee(t) => vec![t.asset_id.as_str()],
+            Tx::SetAssetScript(t) => vec![t.asset_id.as_str()],
+            Tx::InvokeScript(t) => std::iter::once(t.tx.fee_asset_id.as_str())
+                .chain(t.payments.iter().map(|p| p.asset_id.as_str()))
+                .collect(),
+            Tx::UpdateAssetInfo(t) => vec![t.asset_id.as_str()],
+            Tx::Ethereum(t) => t
+                .asset_id
+                .iter()
+                .map(String::as_str)
+                .chain(t.payments.iter().map(|p| p.asset_id.as_str()))
+                .collect(),
+        }
+    }
+}
+
+/// Derives uids from heights: the uids of a height are `height * multiplier + n`
 pub struct TxUidGenerator {
     multiplier: i64,
-    last_height: TxHeight,
-    last_id: TxUid,
+    height: TxHeight,
+    next_id: i64,
 }
 
 impl TxUidGenerator {
     pub const fn new(multiplier: i64) -> Self {
         Self {
             multiplier,
-            last_height: 0,
-            last_id: 0,
+            height: 0,
+            next_id: 0,
         }
     }
 
-    pub fn maybe_update_height(&mut self, height: TxHeight) {
-        if self.last_height < height {
-            self.last_height = height;
-            self.last_id = 0;
-        }
+    pub fn height(&self) -> TxHeight {
+        self.height
+    }
+
+    /// Continues the uids of the height after `last_uid`, the max one already stored for it
+    pub fn start_height(&mut self, height: TxHeight, last_uid: Option<TxUid>) -> Result<(), Error> {
+        let first_uid = height as i64 * self.multiplier;
+        self.next_id = match last_uid {
+            Some(uid) if uid < first_uid || uid >= first_uid + self.multiplier => {
+                return Err(Error::TxUidError(format!(
+                    "stored tx uid {} doesn't belong to height {}",
+                    uid, height
+                )))
+            }
+            Some(uid) => uid - first_uid + 1,
+            None => 0,
+        };
+        self.height = height;
+        Ok(())
     }
 
-    pub fn next(&mut self) -> TxUid {
-        let result = self.last_height as i64 * self.multiplier + self.last_id;
-        self.last_id += 1;
-        result
+    pub fn next(&mut self) -> Result<TxUid, Error> {
+        if self.next_id >= self.multiplier {
+            return Err(Error::TxUidError(format!(
+                "more than {} transactions at height {}",
+                self.multiplier, self.height
+            )));
+        }
+        let result = self.height as i64 * self.multiplier + self.next_id;
+        self.next_id += 1;
+        Ok(result)
     }
 }
 
@@ -153,10 +294,12 @@ impl
                     function_name: None,
                 };
                 let result_tx = match meta.action.as_ref().unwrap() {
-                    EthAction::Transfer(_) => Tx18Combined {
+                    EthAction::Transfer(tmeta) => Tx18Combined {
                         tx: eth_tx,
                         args: vec![],
                         payments: vec![],
+                        recipient_address: into_base58(&tmeta.recipient_address),
+                        asset_id: tmeta.amount.as_ref().map(|a| extract_asset_id(&a.asset_id)),
                     },
                     EthAction::Invoke(imeta) => {
                         eth_tx.function_name = Some(imeta.function_name.clone());
@@ -192,9 +335,17 @@ impl
                                                     .clone(),
                                             ),
                                         ),
-                                        InvokeScriptArgValue::CaseObj(_) => {
-                                            ("case", None, None, None, None, None)
-                                        }
+                                        InvokeScriptArgValue::CaseObj(_) => (
+                                            "case",
+                                            None,
+                                            None,
+                                            None,
+                                            None,
+                                            Some(
+                                                json!(DataEntryTypeValue::from(arg))["value"]
+                                                    .clone(),
+                                            ),
+                                        ),
                                     };
                                     Tx18Args {
                                         tx_uid,
@@ -221,6 +372,8 @@ impl
                                     asset_id: extract_asset_id(&p.asset_id),
                                 })
                                 .collect(),
+                            recipient_address: into_base58(&imeta.d_app_address),
+                            asset_id: None,
                         }
                     }
                 };
@@ -674,9 +827,14 @@ impl
                                     None,
                                     Some(json!(DataEntryTypeValue::from(arg))["value"].clone()),
                                 ),
-                                InvokeScriptArgValue::CaseObj(_) => {
-                                    ("case", None, None, None, None, None)
-                                }
+                                InvokeScriptArgValue::CaseObj(_) => (
+                                    "case",
+                                    None,
+                                    None,
+                                    None,
+                                    None,
+                                    Some(json!(DataEntryTypeValue::from(arg))["value"].clone()),
+                                ),
                             };
                             Tx16Args {
                                 tx_uid,
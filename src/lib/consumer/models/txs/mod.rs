@@ -19,9 +19,13 @@ type TxSenderPubKey = String;
 type TxStatus = String;
 type TxBlockUid = i64;
 
+// `treat_none_as_default_value = false` is required by COPY, and makes INSERT write NULL
+// for `None` too: nullable columns of the tables default to NULL, so the rows are the same
+
 /// Genesis transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_1)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx1 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -44,6 +48,7 @@ pub struct Tx1 {
 /// Payment transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_2)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx2 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -66,6 +71,7 @@ pub struct Tx2 {
 /// Issue transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_3)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx3 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -92,6 +98,7 @@ pub struct Tx3 {
 /// Transfer transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_4)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx4 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -117,6 +124,7 @@ pub struct Tx4 {
 /// Reissue transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_5)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx5 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -139,6 +147,7 @@ pub struct Tx5 {
 /// Burn transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_6)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx6 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -160,6 +169,7 @@ pub struct Tx6 {
 /// Exchange transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_7)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx7 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -188,6 +198,7 @@ pub struct Tx7 {
 /// Lease transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_8)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx8 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -231,6 +242,7 @@ pub struct Tx9Partial {
 /// LeaseCancel transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_9)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx9 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -273,6 +285,7 @@ impl From<(&Tx9Partial, Option<i64>)> for Tx9 {
 /// CreateAlias transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_10)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx10 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -293,6 +306,7 @@ pub struct Tx10 {
 /// MassTransfer transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_11)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx11 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -314,6 +328,7 @@ pub struct Tx11 {
 /// MassTransfer transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_11_transfers)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx11Transfers {
     pub tx_uid: TxUid,
     pub recipient_address: String,
@@ -333,6 +348,7 @@ pub struct Tx11Combined {
 /// DataTransaction transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_12)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx12 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -352,6 +368,7 @@ pub struct Tx12 {
 /// DataTransaction transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_12_data)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx12Data {
     pub tx_uid: TxUid,
     pub data_key: String,
@@ -374,6 +391,7 @@ pub struct Tx12Combined {
 /// SetScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_13)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx13 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -394,6 +412,7 @@ pub struct Tx13 {
 /// SponsorFee transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_14)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx14 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -415,6 +434,7 @@ pub struct Tx14 {
 /// SetAssetScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_15)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx15 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -436,6 +456,7 @@ pub struct Tx15 {
 /// InvokeScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_16)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx16 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -459,6 +480,7 @@ pub struct Tx16 {
 /// InvokeScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_16_args)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx16Args {
     pub tx_uid: TxUid,
     pub arg_type: String,
@@ -474,6 +496,7 @@ pub struct Tx16Args {
 /// InvokeScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_16_payment)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx16Payment {
     pub tx_uid: TxUid,
     pub amount: i64,
@@ -493,6 +516,7 @@ pub struct Tx16Combined {
 /// UpdateAssetInfo transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_17)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx17 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -515,6 +539,7 @@ pub struct Tx17 {
 /// Ethereum transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_18)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx18 {
     pub uid: TxUid,
     pub height: TxHeight,
@@ -536,6 +561,7 @@ pub struct Tx18 {
 /// Ethereum InvokeScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_18_args)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx18Args {
     pub tx_uid: TxUid,
     pub arg_type: String,
@@ -551,6 +577,7 @@ pub struct Tx18Args {
 /// Ethereum InvokeScript transaction
 #[derive(Clone, Debug, Insertable)]
 #[diesel(table_name = txs_18_payment)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Tx18Payment {
     pub tx_uid: TxUid,
     pub amount: i64,
@@ -565,4 +592,8 @@ pub struct Tx18Combined {
     pub tx: Tx18,
     pub args: Vec<Tx18Args>,
     pub payments: Vec<Tx18Payment>,
+    /// Transfer recipient or invoked dApp, for filtering only
+    pub recipient_address: String,
+    /// Transferred asset, for filtering only
+    pub asset_id: Option<String>,
 }
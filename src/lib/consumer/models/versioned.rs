@@ -0,0 +1,170 @@
+//! Rows of versioned tables. Every change of a key is stored as a new row with its own `uid`,
+//! and is superseded by the uid of the next version of the key, or by `MAX_UID` while current.
+
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{
+    asset_tickers::{AssetTickerOverride, InsertableAssetTicker},
+    assets::{AssetOverride, AssetUpdate},
+    assets_metadata::{AssetMetadataOverride, InsertableAssetMetadata},
+    balance_history::{BalanceHistoryOverride, InsertableBalanceHistory},
+    data_entries::{DataEntryOverride, InsertableDataEntry},
+    leases::{InsertableLease, LeaseOverride},
+};
+
+pub const MAX_UID: i64 = std::i64::MAX - 1;
+
+pub trait Versioned: Clone {
+    type Key: Clone + Eq + Hash;
+    /// Closes the stored current version of a key
+    type Override;
+
+    fn key(&self) -> Self::Key;
+    fn uid(&self) -> i64;
+    fn superseded_by(&self) -> i64;
+    fn set_superseded_by(&mut self, superseded_by: i64);
+    fn block_uid(&self) -> i64;
+    fn set_block_uid(&mut self, block_uid: i64);
+    /// Override superseding the stored current version of the key by this one
+    fn to_override(&self) -> Self::Override;
+}
+
+macro_rules! impl_versioned {
+    ($t:ty, $key:ty, |$row:ident| $get_key:expr, $override:ty, |$o:ident| $to_override:expr) => {
+        impl Versioned for $t {
+            type Key = $key;
+            type Override = $override;
+
+            fn key(&self) -> Self::Key {
+                let $row = self;
+                $get_key
+            }
+
+            fn uid(&self) -> i64 {
+                self.uid
+            }
+
+            fn superseded_by(&self) -> i64 {
+                self.superseded_by
+            }
+
+            fn set_superseded_by(&mut self, superseded_by: i64) {
+                self.superseded_by = superseded_by;
+            }
+
+            fn block_uid(&self) -> i64 {
+                self.block_uid
+            }
+
+            fn set_block_uid(&mut self, block_uid: i64) {
+                self.block_uid = block_uid;
+            }
+
+            fn to_override(&self) -> Self::Override {
+                let $o = self;
+                $to_override
+            }
+        }
+    };
+}
+
+impl_versioned!(
+    AssetUpdate,
+    String,
+    |r| r.asset_id.clone(),
+    AssetOverride,
+    |r| AssetOverride {
+        superseded_by: r.uid,
+        id: r.asset_id.clone(),
+    }
+);
+impl_versioned!(
+    InsertableAssetTicker,
+    String,
+    |r| r.asset_id.clone(),
+    AssetTickerOverride,
+    |r| AssetTickerOverride {
+        superseded_by: r.uid,
+        asset_id: r.asset_id.clone(),
+    }
+);
+impl_versioned!(
+    InsertableAssetMetadata,
+    String,
+    |r| r.asset_id.clone(),
+    AssetMetadataOverride,
+    |r| AssetMetadataOverride {
+        superseded_by: r.uid,
+        asset_id: r.asset_id.clone(),
+    }
+);
+impl_versioned!(
+    InsertableBalanceHistory,
+    (String, String),
+    |r| (r.address.clone(), r.asset_id.clone()),
+    BalanceHistoryOverride,
+    |r| BalanceHistoryOverride {
+        superseded_by: r.uid,
+        address: r.address.clone(),
+        asset_id: r.asset_id.clone(),
+    }
+);
+impl_versioned!(
+    InsertableLease,
+    String,
+    |r| r.lease_id.clone(),
+    LeaseOverride,
+    |r| LeaseOverride {
+        superseded_by: r.uid,
+        lease_id: r.lease_id.clone(),
+    }
+);
+impl_versioned!(
+    InsertableDataEntry,
+    (String, String),
+    |r| (r.address.clone(), r.key.clone()),
+    DataEntryOverride,
+    |r| DataEntryOverride {
+        superseded_by: r.uid,
+        address: r.address.clone(),
+        key: r.key.clone(),
+    }
+);
+
+/// Sorts new versions by uid and supersedes each one by the next version of its key,
+/// the last ones stay current. Returns them with the overrides closing the stored versions.
+pub fn chain_versions<T: Versioned>(mut versions: Vec<T>) -> (Vec<T>, Vec<T::Override>) {
+    versions.sort_by_key(T::uid);
+
+    let mut next_uids = HashMap::new();
+    for version in versions.iter_mut().rev() {
+        let superseded_by = next_uids
+            .insert(version.key(), version.uid())
+            .unwrap_or(MAX_UID);
+        version.set_superseded_by(superseded_by);
+    }
+
+    let overrides = versions
+        .iter()
+        .unique_by(|version| version.key())
+        .map(T::to_override)
+        .collect();
+
+    (versions, overrides)
+}
+
+/// Uid of the first rolled back version of every key, which the previous version is superseded by
+pub fn lowest_deleted_uids<T: Eq + Hash>(deleted: Vec<T>, uid: impl Fn(&T) -> i64) -> Vec<i64> {
+    deleted
+        .into_iter()
+        .map(|row| {
+            let uid = uid(&row);
+            (row, uid)
+        })
+        .into_grouping_map()
+        .min()
+        .into_values()
+        .collect()
+}
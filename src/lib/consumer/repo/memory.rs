@@ -0,0 +1,935 @@
+//! In-memory implementation of the repo, used to test the consumer pipeline without a database.
+//!
+//! It models blocks/microblocks, versioned tables (`uid`, `superseded_by`, `block_uid`)
+//! and transactions the same way the Postgres queries do. Minute candles are modeled
+//! only by their start, candle values and pairs are computed by SQL from `txs_7`
+//! and are not modeled.
+
+use std::collections::BTreeSet;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Timelike};
+
+use super::super::UidHeight;
+use super::{Repo, RepoOperations};
+use crate::consumer::models::{
+    aliases::Alias,
+    asset_tickers::{AssetTickerOverride, DeletedAssetTicker, InsertableAssetTicker},
+    assets::{AssetOrigin, AssetOverride, AssetUpdate, DeletedAsset},
+    assets_metadata::{AssetMetadataOverride, DeletedAssetMetadata, InsertableAssetMetadata},
+    balance_history::{BalanceHistoryOverride, DeletedBalanceHistory, InsertableBalanceHistory},
+    block_microblock::BlockMicroblock,
+    data_entries::{DataEntryOverride, DeletedDataEntry, InsertableDataEntry},
+    ingest_events::InsertableIngestEvent,
+    leases::{DeletedLease, InsertableLease, LeaseOverride},
+    txs::*,
+    versioned::{Versioned, MAX_UID},
+    waves_data::WavesData,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryRepo {
+    state: Arc<Mutex<MemoryRepoOperations>>,
+}
+
+pub fn new() -> MemoryRepo {
+    MemoryRepo::default()
+}
+
+impl MemoryRepo {
+    /// Copy of the committed state
+    pub fn snapshot(&self) -> MemoryRepoOperations {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Repo for MemoryRepo {
+    type Operations<'c> = MemoryRepoOperations;
+
+    async fn transaction<F, R>(&self, f: F) -> Result<R>
+    where
+        F: for<'conn> FnOnce(&mut Self::Operations<'conn>) -> Result<R>,
+        F: Send + 'static,
+        R: Send + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+        // changes are made on a copy, so a failed transaction leaves the state untouched
+        let mut ops = state.clone();
+        let result = f(&mut ops)?;
+        *state = ops;
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryBlockMicroblock {
+    pub uid: i64,
+    pub id: String,
+    pub height: i32,
+    pub time_stamp: Option<NaiveDateTime>,
+}
+
+/// Common columns of all `txs_*` tables
+#[derive(Clone, Debug)]
+pub struct MemoryTx {
+    pub uid: i64,
+    pub id: String,
+    pub tx_type: i16,
+    pub height: i32,
+    pub time_stamp: NaiveDateTime,
+    pub block_uid: i64,
+}
+
+/// Table of versioned rows together with its uid sequence
+#[derive(Clone, Debug)]
+pub struct VersionedTable<T> {
+    pub rows: Vec<T>,
+    next_uid: i64,
+}
+
+impl<T> Default for VersionedTable<T> {
+    fn default() -> Self {
+        VersionedTable {
+            rows: vec![],
+            next_uid: 1,
+        }
+    }
+}
+
+impl<T: Versioned> VersionedTable<T> {
+    /// Current version of the row, i.e. one not superseded yet
+    pub fn current(&self, key: &T::Key) -> Option<&T> {
+        self.rows
+            .iter()
+            .find(|r| &r.key() == key && r.superseded_by() == MAX_UID)
+    }
+
+    /// All versions of the row ordered by uid
+    pub fn versions(&self, key: &T::Key) -> Vec<&T> {
+        let mut versions = self
+            .rows
+            .iter()
+            .filter(|r| &r.key() == key)
+            .collect::<Vec<_>>();
+        versions.sort_by_key(|r| r.uid());
+        versions
+    }
+
+    fn next_uid(&mut self) -> i64 {
+        let uid = self.next_uid;
+        self.next_uid += 1;
+        uid
+    }
+
+    fn set_next_uid(&mut self, new_uid: i64) {
+        self.next_uid = new_uid;
+    }
+
+    fn insert(&mut self, rows: &[T]) {
+        self.rows.extend_from_slice(rows);
+    }
+
+    fn close(&mut self, overrides: impl IntoIterator<Item = (T::Key, i64)>) {
+        for (key, superseded_by) in overrides {
+            self.rows
+                .iter_mut()
+                .filter(|r| r.key() == key && r.superseded_by() == MAX_UID)
+                .for_each(|r| r.set_superseded_by(superseded_by));
+        }
+    }
+
+    fn reopen(&mut self, current_superseded_by: &[i64]) {
+        self.rows
+            .iter_mut()
+            .filter(|r| current_superseded_by.contains(&r.superseded_by()))
+            .for_each(|r| r.set_superseded_by(MAX_UID));
+    }
+
+    fn update_block_references(&mut self, block_uid: i64) {
+        self.rows
+            .iter_mut()
+            .filter(|r| r.block_uid() > block_uid)
+            .for_each(|r| r.set_block_uid(block_uid));
+    }
+
+    fn rollback(&mut self, block_uid: i64) -> Vec<T> {
+        let (deleted, kept) = self
+            .rows
+            .drain(..)
+            .partition(|r: &T| r.block_uid() > block_uid);
+        self.rows = kept;
+        deleted
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryRepoOperations {
+    pub blocks_microblocks: Vec<MemoryBlockMicroblock>,
+    next_block_uid: i64,
+    pub waves_data: Vec<WavesData>,
+    pub asset_updates: VersionedTable<AssetUpdate>,
+    pub asset_origins: Vec<AssetOrigin>,
+    pub asset_tickers: VersionedTable<InsertableAssetTicker>,
+    pub assets_metadata: VersionedTable<InsertableAssetMetadata>,
+    pub txs: Vec<MemoryTx>,
+    pub balance_history: VersionedTable<InsertableBalanceHistory>,
+    pub leases: VersionedTable<InsertableLease>,
+    pub data_entries: VersionedTable<InsertableDataEntry>,
+    pub aliases: Vec<Alias>,
+    /// in `seq` order
+    pub ingest_events: Vec<InsertableIngestEvent>,
+    /// `(channel, payload)` in the order they were sent
+    pub notifications: Vec<(String, String)>,
+    /// start of every minute candle
+    pub minute_candles: BTreeSet<NaiveDateTime>,
+}
+
+impl Default for MemoryRepoOperations {
+    fn default() -> Self {
+        MemoryRepoOperations {
+            blocks_microblocks: vec![],
+            next_block_uid: 1,
+            waves_data: vec![],
+            asset_updates: VersionedTable::default(),
+            asset_origins: vec![],
+            asset_tickers: VersionedTable::default(),
+            assets_metadata: VersionedTable::default(),
+            txs: vec![],
+            balance_history: VersionedTable::default(),
+            leases: VersionedTable::default(),
+            data_entries: VersionedTable::default(),
+            aliases: vec![],
+            ingest_events: vec![],
+            notifications: vec![],
+            minute_candles: BTreeSet::new(),
+        }
+    }
+}
+
+macro_rules! memory_txs {
+    ($txs:expr) => {
+        $txs.map(|t| MemoryTx {
+            uid: t.uid,
+            id: t.id.clone(),
+            tx_type: t.tx_type,
+            height: t.height,
+            time_stamp: t.time_stamp,
+            block_uid: t.block_uid,
+        })
+    };
+}
+
+impl MemoryRepoOperations {
+    fn push_txs(&mut self, txs: impl Iterator<Item = MemoryTx>) -> Result<()> {
+        for tx in txs {
+            if self.txs.iter().any(|t| t.uid == tx.uid) {
+                return Err(anyhow!("Transaction with uid {} already exists", tx.uid));
+            }
+            self.txs.push(tx);
+        }
+        Ok(())
+    }
+
+    fn exchanges(&self) -> impl Iterator<Item = &MemoryTx> {
+        self.txs.iter().filter(|t| t.tx_type == 7)
+    }
+
+    fn first_exchange_minute_in_block(&self, block_uid: i64) -> Option<NaiveDateTime> {
+        self.exchanges()
+            .filter(|t| t.block_uid == block_uid)
+            .map(|t| minute_start(t.time_stamp))
+            .min()
+    }
+}
+
+fn minute_start(ts: NaiveDateTime) -> NaiveDateTime {
+    ts.with_second(0)
+        .and_then(|ts| ts.with_nanosecond(0))
+        .unwrap()
+}
+
+impl RepoOperations for MemoryRepoOperations {
+    //
+    // COMMON
+    //
+
+    fn get_current_height(&mut self) -> Result<i32> {
+        Ok(self
+            .blocks_microblocks
+            .iter()
+            .map(|b| b.height)
+            .max()
+            .unwrap_or(0))
+    }
+
+    fn get_blocks_rollback_to(
+        &mut self,
+        depth: NonZeroU32,
+        seq_step: NonZeroU32,
+    ) -> Result<Option<Vec<UidHeight>>> {
+        let depth = depth.into();
+        let current_height = self.get_current_height()? as u32;
+        let rollback_step = u32::min(seq_step.into(), depth);
+        let starting_height = current_height.saturating_sub(rollback_step);
+        let final_height = current_height.saturating_sub(depth);
+
+        let mut heights_rollback_to = ((final_height + 1)..=starting_height)
+            .rev()
+            .step_by(rollback_step as usize)
+            .map(|h| h as i32)
+            .collect::<Vec<_>>();
+
+        heights_rollback_to.push(final_height as i32);
+
+        let mut blocks = self
+            .blocks_microblocks
+            .iter()
+            .filter(|b| heights_rollback_to.contains(&b.height))
+            .map(|b| UidHeight {
+                uid: b.uid,
+                height: b.height,
+            })
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|b| -b.uid);
+
+        Ok(Some(blocks))
+    }
+
+    fn get_block_uid_height(&mut self, block_id: &str) -> Result<UidHeight> {
+        self.blocks_microblocks
+            .iter()
+            .find(|b| b.id == block_id)
+            .map(|b| UidHeight {
+                uid: b.uid,
+                height: b.height,
+            })
+            .ok_or_else(|| anyhow!("Cannot get block_uid by block id {}", block_id))
+    }
+
+    fn get_key_block_uid(&mut self) -> Result<i64> {
+        self.blocks_microblocks
+            .iter()
+            .filter(|b| b.time_stamp.is_some())
+            .map(|b| b.uid)
+            .max()
+            .ok_or_else(|| anyhow!("Cannot get key block uid"))
+    }
+
+    fn get_total_block_id(&mut self) -> Result<Option<String>> {
+        Ok(self
+            .blocks_microblocks
+            .iter()
+            .filter(|b| b.time_stamp.is_none())
+            .max_by_key(|b| b.uid)
+            .map(|b| b.id.clone()))
+    }
+
+    fn get_last_block_id(&mut self) -> Result<Option<String>> {
+        Ok(self
+            .blocks_microblocks
+            .iter()
+            .max_by_key(|b| b.uid)
+            .map(|b| b.id.clone()))
+    }
+
+    fn insert_blocks_or_microblocks(&mut self, blocks: &Vec<BlockMicroblock>) -> Result<Vec<i64>> {
+        let mut uids = vec![];
+        for block in blocks {
+            if self.blocks_microblocks.iter().any(|b| b.id == block.id) {
+                return Err(anyhow!("Block/microblock {} already exists", block.id));
+            }
+            let uid = self.next_block_uid;
+            self.next_block_uid += 1;
+            self.blocks_microblocks.push(MemoryBlockMicroblock {
+                uid,
+                id: block.id.clone(),
+                height: block.height,
+                time_stamp: block.time_stamp,
+            });
+            uids.push(uid);
+        }
+        Ok(uids)
+    }
+
+    fn change_block_id(&mut self, block_uid: i64, new_block_id: &str) -> Result<()> {
+        self.blocks_microblocks
+            .iter_mut()
+            .filter(|b| b.uid == block_uid)
+            .for_each(|b| b.id = new_block_id.to_owned());
+        Ok(())
+    }
+
+    fn delete_microblocks(&mut self) -> Result<()> {
+        self.blocks_microblocks.retain(|b| b.time_stamp.is_some());
+        Ok(())
+    }
+
+    fn get_block_uids_after(&mut self, block_uid: i64) -> Result<Vec<i64>> {
+        Ok(self
+            .blocks_microblocks
+            .iter()
+            .map(|b| b.uid)
+            .filter(|&uid| uid > block_uid)
+            .collect())
+    }
+
+    fn rollback_blocks_microblocks(&mut self, block_uid: i64) -> Result<Option<i32>> {
+        let removed_height = self
+            .blocks_microblocks
+            .iter()
+            .filter(|b| b.uid > block_uid)
+            .map(|b| b.height)
+            .max();
+        self.blocks_microblocks.retain(|b| b.uid <= block_uid);
+        Ok(removed_height)
+    }
+
+    fn insert_waves_data(&mut self, waves_data: &Vec<WavesData>) -> Result<()> {
+        for data in waves_data {
+            if !self.waves_data.iter().any(|d| d.quantity == data.quantity) {
+                self.waves_data.push(data.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, channel: &str, payload: &str) -> Result<()> {
+        self.notifications
+            .push((channel.to_owned(), payload.to_owned()));
+        Ok(())
+    }
+
+    //
+    // ASSETS
+    //
+
+    fn get_next_assets_uid(&mut self) -> Result<i64> {
+        Ok(self.asset_updates.next_uid())
+    }
+
+    fn insert_asset_updates(&mut self, updates: &Vec<AssetUpdate>) -> Result<()> {
+        self.asset_updates.insert(updates);
+        Ok(())
+    }
+
+    fn insert_asset_origins(&mut self, origins: &Vec<AssetOrigin>) -> Result<()> {
+        for origin in origins {
+            if !self
+                .asset_origins
+                .iter()
+                .any(|o| o.asset_id == origin.asset_id)
+            {
+                self.asset_origins.push(origin.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn update_assets_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.asset_updates.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn close_assets_superseded_by(&mut self, updates: &Vec<AssetOverride>) -> Result<()> {
+        self.asset_updates
+            .close(updates.iter().map(|u| (u.id.clone(), u.superseded_by)));
+        Ok(())
+    }
+
+    fn reopen_assets_superseded_by(&mut self, current_superseded_by: &Vec<i64>) -> Result<()> {
+        self.asset_updates.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn set_assets_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.asset_updates.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn rollback_assets(&mut self, block_uid: i64) -> Result<Vec<DeletedAsset>> {
+        Ok(self
+            .asset_updates
+            .rollback(block_uid)
+            .into_iter()
+            .map(|a| DeletedAsset {
+                uid: a.uid,
+                id: a.asset_id,
+            })
+            .collect())
+    }
+
+    fn assets_gt_block_uid(&mut self, block_uid: i64) -> Result<Vec<i64>> {
+        Ok(self
+            .asset_updates
+            .rows
+            .iter()
+            .filter(|a| a.block_uid > block_uid)
+            .map(|a| a.uid)
+            .collect())
+    }
+
+    fn insert_asset_tickers(&mut self, tickers: &Vec<InsertableAssetTicker>) -> Result<()> {
+        self.asset_tickers.insert(tickers);
+        Ok(())
+    }
+
+    fn rollback_asset_tickers(&mut self, block_uid: &i64) -> Result<Vec<DeletedAssetTicker>> {
+        Ok(self
+            .asset_tickers
+            .rollback(*block_uid)
+            .into_iter()
+            .map(|t| DeletedAssetTicker {
+                uid: t.uid,
+                asset_id: t.asset_id,
+            })
+            .collect())
+    }
+
+    fn update_asset_tickers_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.asset_tickers.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn reopen_asset_tickers_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        self.asset_tickers.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn close_asset_tickers_superseded_by(
+        &mut self,
+        updates: &Vec<AssetTickerOverride>,
+    ) -> Result<()> {
+        self.asset_tickers.close(
+            updates
+                .iter()
+                .map(|u| (u.asset_id.clone(), u.superseded_by)),
+        );
+        Ok(())
+    }
+
+    fn set_asset_tickers_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.asset_tickers.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn get_next_asset_tickers_uid(&mut self) -> Result<i64> {
+        Ok(self.asset_tickers.next_uid())
+    }
+
+    fn get_current_assets_metadata(
+        &mut self,
+        asset_ids: &Vec<String>,
+    ) -> Result<Vec<InsertableAssetMetadata>> {
+        Ok(asset_ids
+            .iter()
+            .filter_map(|id| self.assets_metadata.current(id).cloned())
+            .collect())
+    }
+
+    fn insert_assets_metadata(&mut self, metadata: &Vec<InsertableAssetMetadata>) -> Result<()> {
+        self.assets_metadata.insert(metadata);
+        Ok(())
+    }
+
+    fn rollback_assets_metadata(&mut self, block_uid: i64) -> Result<Vec<DeletedAssetMetadata>> {
+        Ok(self
+            .assets_metadata
+            .rollback(block_uid)
+            .into_iter()
+            .map(|m| DeletedAssetMetadata {
+                uid: m.uid,
+                asset_id: m.asset_id,
+            })
+            .collect())
+    }
+
+    fn update_assets_metadata_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.assets_metadata.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn reopen_assets_metadata_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        self.assets_metadata.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn close_assets_metadata_superseded_by(
+        &mut self,
+        updates: &Vec<AssetMetadataOverride>,
+    ) -> Result<()> {
+        self.assets_metadata.close(
+            updates
+                .iter()
+                .map(|u| (u.asset_id.clone(), u.superseded_by)),
+        );
+        Ok(())
+    }
+
+    fn set_assets_metadata_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.assets_metadata.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn get_next_assets_metadata_uid(&mut self) -> Result<i64> {
+        Ok(self.assets_metadata.next_uid())
+    }
+
+    //
+    // TRANSACTIONS
+    //
+
+    fn update_transactions_references(&mut self, block_uid: i64) -> Result<()> {
+        self.txs
+            .iter_mut()
+            .filter(|t| t.block_uid > block_uid)
+            .for_each(|t| t.block_uid = block_uid);
+        Ok(())
+    }
+
+    fn rollback_transactions(&mut self, block_uid: i64) -> Result<()> {
+        self.txs.retain(|t| t.block_uid <= block_uid);
+        Ok(())
+    }
+
+    fn create_txs_partitions(&mut self, _height: i32) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_max_tx_uid_at_height(&mut self, height: i32) -> Result<Option<i64>> {
+        Ok(self
+            .txs
+            .iter()
+            .filter(|t| t.height == height)
+            .map(|t| t.uid)
+            .max())
+    }
+
+    fn insert_txs_1(&mut self, txs: Vec<Tx1>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_2(&mut self, txs: Vec<Tx2>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_3(&mut self, txs: Vec<Tx3>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_4(&mut self, txs: Vec<Tx4>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_5(&mut self, txs: Vec<Tx5>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_6(&mut self, txs: Vec<Tx6>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_7(&mut self, txs: Vec<Tx7>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_8(&mut self, txs: Vec<Tx8>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_9(&mut self, txs: Vec<Tx9Partial>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_10(&mut self, txs: Vec<Tx10>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_11(&mut self, txs: Vec<Tx11Combined>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter().map(|t| &t.tx)))
+    }
+
+    fn insert_txs_12(&mut self, txs: Vec<Tx12Combined>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter().map(|t| &t.tx)))
+    }
+
+    fn insert_txs_13(&mut self, txs: Vec<Tx13>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_14(&mut self, txs: Vec<Tx14>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_15(&mut self, txs: Vec<Tx15>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_16(&mut self, txs: Vec<Tx16Combined>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter().map(|t| &t.tx)))
+    }
+
+    fn insert_txs_17(&mut self, txs: Vec<Tx17>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter()))
+    }
+
+    fn insert_txs_18(&mut self, txs: Vec<Tx18Combined>) -> Result<()> {
+        self.push_txs(memory_txs!(txs.iter().map(|t| &t.tx)))
+    }
+
+    //
+    // BALANCES
+    //
+
+    fn get_next_balance_history_uid(&mut self) -> Result<i64> {
+        Ok(self.balance_history.next_uid())
+    }
+
+    fn insert_balance_history(&mut self, balances: &Vec<InsertableBalanceHistory>) -> Result<()> {
+        self.balance_history.insert(balances);
+        Ok(())
+    }
+
+    fn update_balance_history_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.balance_history.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn close_balance_history_superseded_by(
+        &mut self,
+        updates: &Vec<BalanceHistoryOverride>,
+    ) -> Result<()> {
+        self.balance_history.close(
+            updates
+                .iter()
+                .map(|u| ((u.address.clone(), u.asset_id.clone()), u.superseded_by)),
+        );
+        Ok(())
+    }
+
+    fn reopen_balance_history_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        self.balance_history.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn set_balance_history_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.balance_history.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn rollback_balance_history(&mut self, block_uid: i64) -> Result<Vec<DeletedBalanceHistory>> {
+        Ok(self
+            .balance_history
+            .rollback(block_uid)
+            .into_iter()
+            .map(|b| DeletedBalanceHistory {
+                uid: b.uid,
+                address: b.address,
+                asset_id: b.asset_id,
+            })
+            .collect())
+    }
+
+    //
+    // LEASES
+    //
+
+    fn get_next_leases_uid(&mut self) -> Result<i64> {
+        Ok(self.leases.next_uid())
+    }
+
+    fn insert_leases(&mut self, leases: &Vec<InsertableLease>) -> Result<()> {
+        self.leases.insert(leases);
+        Ok(())
+    }
+
+    fn update_leases_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.leases.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn close_leases_superseded_by(&mut self, updates: &Vec<LeaseOverride>) -> Result<()> {
+        self.leases.close(
+            updates
+                .iter()
+                .map(|u| (u.lease_id.clone(), u.superseded_by)),
+        );
+        Ok(())
+    }
+
+    fn reopen_leases_superseded_by(&mut self, current_superseded_by: &Vec<i64>) -> Result<()> {
+        self.leases.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn set_leases_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.leases.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn rollback_leases(&mut self, block_uid: i64) -> Result<Vec<DeletedLease>> {
+        Ok(self
+            .leases
+            .rollback(block_uid)
+            .into_iter()
+            .map(|l| DeletedLease {
+                uid: l.uid,
+                lease_id: l.lease_id,
+            })
+            .collect())
+    }
+
+    //
+    // DATA ENTRIES
+    //
+
+    fn get_next_data_entries_uid(&mut self) -> Result<i64> {
+        Ok(self.data_entries.next_uid())
+    }
+
+    fn insert_data_entries(&mut self, entries: &Vec<InsertableDataEntry>) -> Result<()> {
+        self.data_entries.insert(entries);
+        Ok(())
+    }
+
+    fn update_data_entries_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.data_entries.update_block_references(block_uid);
+        Ok(())
+    }
+
+    fn close_data_entries_superseded_by(&mut self, updates: &Vec<DataEntryOverride>) -> Result<()> {
+        self.data_entries.close(
+            updates
+                .iter()
+                .map(|u| ((u.address.clone(), u.key.clone()), u.superseded_by)),
+        );
+        Ok(())
+    }
+
+    fn reopen_data_entries_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        self.data_entries.reopen(current_superseded_by);
+        Ok(())
+    }
+
+    fn set_data_entries_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        self.data_entries.set_next_uid(new_uid);
+        Ok(())
+    }
+
+    fn rollback_data_entries(&mut self, block_uid: i64) -> Result<Vec<DeletedDataEntry>> {
+        Ok(self
+            .data_entries
+            .rollback(block_uid)
+            .into_iter()
+            .map(|d| DeletedDataEntry {
+                uid: d.uid,
+                address: d.address,
+                key: d.key,
+            })
+            .collect())
+    }
+
+    //
+    // ALIASES
+    //
+
+    fn get_aliases(&mut self, aliases: &Vec<String>) -> Result<Vec<Alias>> {
+        Ok(self
+            .aliases
+            .iter()
+            .filter(|a| aliases.contains(&a.alias))
+            .cloned()
+            .collect())
+    }
+
+    fn insert_aliases(&mut self, aliases: &Vec<Alias>) -> Result<()> {
+        self.aliases.extend_from_slice(aliases);
+        Ok(())
+    }
+
+    fn update_aliases_block_references(&mut self, block_uid: i64) -> Result<()> {
+        self.aliases
+            .iter_mut()
+            .filter(|a| a.block_uid > block_uid)
+            .for_each(|a| a.block_uid = block_uid);
+        Ok(())
+    }
+
+    fn rollback_aliases(&mut self, block_uid: i64) -> Result<()> {
+        self.aliases.retain(|a| a.block_uid <= block_uid);
+        Ok(())
+    }
+
+    //
+    // INGEST EVENTS
+    //
+
+    fn insert_ingest_events(&mut self, events: &Vec<InsertableIngestEvent>) -> Result<()> {
+        self.ingest_events.extend_from_slice(events);
+        Ok(())
+    }
+
+    //
+    // CANDLES
+    //
+
+    fn calculate_candles_since_block_uid(&mut self, block_uid: i64) -> Result<()> {
+        match self.first_exchange_minute_in_block(block_uid) {
+            Some(ts) => self.calculate_minute_candles(ts),
+            None => Ok(()),
+        }
+    }
+
+    fn calculate_minute_candles(&mut self, ts: NaiveDateTime) -> Result<()> {
+        let minutes = self
+            .exchanges()
+            .filter(|t| t.time_stamp >= ts)
+            .map(|t| minute_start(t.time_stamp))
+            .collect::<Vec<_>>();
+        self.minute_candles.extend(minutes);
+        Ok(())
+    }
+
+    fn calculate_non_minute_candles(&mut self, _ts: NaiveDateTime) -> Result<()> {
+        Ok(())
+    }
+
+    fn rollback_candles(&mut self, block_uid: i64) -> Result<()> {
+        if let Some(ts) = self.first_exchange_minute_in_block(block_uid) {
+            self.minute_candles.retain(|&m| m <= ts);
+        }
+        Ok(())
+    }
+
+    fn get_candles_outdated_since(&mut self) -> Result<Option<NaiveDateTime>> {
+        let last_minute_candle_start = self.minute_candles.last().copied();
+        Ok(self
+            .exchanges()
+            .map(|t| t.time_stamp)
+            .filter(|&ts| last_minute_candle_start.map_or(true, |m| ts >= m))
+            .min())
+    }
+
+    fn calculate_pairs(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn update_pairs(&mut self, _block_uid: i64) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
+#[cfg(test)]
+pub mod memory;
 pub mod pg;
+#[cfg(test)]
+pub mod test_db;
 
 use std::num::NonZeroU32;
 
@@ -7,9 +11,15 @@ use async_trait::async_trait;
 use chrono::NaiveDateTime;
 
 use super::models::{
+    aliases::Alias,
     asset_tickers::{AssetTickerOverride, DeletedAssetTicker, InsertableAssetTicker},
     assets::{AssetOrigin, AssetOverride, AssetUpdate, DeletedAsset},
+    assets_metadata::{AssetMetadataOverride, DeletedAssetMetadata, InsertableAssetMetadata},
+    balance_history::{BalanceHistoryOverride, DeletedBalanceHistory, InsertableBalanceHistory},
     block_microblock::BlockMicroblock,
+    data_entries::{DataEntryOverride, DeletedDataEntry, InsertableDataEntry},
+    ingest_events::InsertableIngestEvent,
+    leases::{DeletedLease, InsertableLease, LeaseOverride},
     txs::*,
     waves_data::WavesData,
 };
@@ -45,16 +55,25 @@ pub trait RepoOperations {
 
     fn get_total_block_id(&mut self) -> Result<Option<String>>;
 
+    /// Id of the last stored block or microblock
+    fn get_last_block_id(&mut self) -> Result<Option<String>>;
+
     fn insert_blocks_or_microblocks(&mut self, blocks: &Vec<BlockMicroblock>) -> Result<Vec<i64>>;
 
     fn change_block_id(&mut self, block_uid: i64, new_block_id: &str) -> Result<()>;
 
     fn delete_microblocks(&mut self) -> Result<()>;
 
-    fn rollback_blocks_microblocks(&mut self, block_uid: i64) -> Result<()>;
+    fn get_block_uids_after(&mut self, block_uid: i64) -> Result<Vec<i64>>;
+
+    /// Returns the highest removed height, if any blocks were removed
+    fn rollback_blocks_microblocks(&mut self, block_uid: i64) -> Result<Option<i32>>;
 
     fn insert_waves_data(&mut self, waves_data: &Vec<WavesData>) -> Result<()>;
 
+    /// Delivered to listeners of `channel` once the transaction is committed
+    fn notify(&mut self, channel: &str, payload: &str) -> Result<()>;
+
     //
     // ASSETS
     //
@@ -97,6 +116,31 @@ pub trait RepoOperations {
 
     fn get_next_asset_tickers_uid(&mut self) -> Result<i64>;
 
+    fn get_current_assets_metadata(
+        &mut self,
+        asset_ids: &Vec<String>,
+    ) -> Result<Vec<InsertableAssetMetadata>>;
+
+    fn insert_assets_metadata(&mut self, metadata: &Vec<InsertableAssetMetadata>) -> Result<()>;
+
+    fn rollback_assets_metadata(&mut self, block_uid: i64) -> Result<Vec<DeletedAssetMetadata>>;
+
+    fn update_assets_metadata_block_references(&mut self, block_uid: i64) -> Result<()>;
+
+    fn reopen_assets_metadata_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()>;
+
+    fn close_assets_metadata_superseded_by(
+        &mut self,
+        updates: &Vec<AssetMetadataOverride>,
+    ) -> Result<()>;
+
+    fn set_assets_metadata_next_update_uid(&mut self, new_uid: i64) -> Result<()>;
+
+    fn get_next_assets_metadata_uid(&mut self) -> Result<i64>;
+
     //
     // TRANSACTIONS
     //
@@ -105,6 +149,11 @@ pub trait RepoOperations {
 
     fn rollback_transactions(&mut self, block_uid: i64) -> Result<()>;
 
+    /// Creates the txs_N partitions missing up to a partition ahead of `height`
+    fn create_txs_partitions(&mut self, height: i32) -> Result<()>;
+
+    fn get_max_tx_uid_at_height(&mut self, height: i32) -> Result<Option<i64>>;
+
     fn insert_txs_1(&mut self, txs: Vec<Tx1>) -> Result<()>;
 
     fn insert_txs_2(&mut self, txs: Vec<Tx2>) -> Result<()>;
@@ -141,6 +190,85 @@ pub trait RepoOperations {
 
     fn insert_txs_18(&mut self, txs: Vec<Tx18Combined>) -> Result<()>;
 
+    //
+    // BALANCES
+    //
+
+    fn get_next_balance_history_uid(&mut self) -> Result<i64>;
+
+    fn insert_balance_history(&mut self, balances: &Vec<InsertableBalanceHistory>) -> Result<()>;
+
+    fn update_balance_history_block_references(&mut self, block_uid: i64) -> Result<()>;
+
+    fn close_balance_history_superseded_by(
+        &mut self,
+        updates: &Vec<BalanceHistoryOverride>,
+    ) -> Result<()>;
+
+    fn reopen_balance_history_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()>;
+
+    fn set_balance_history_next_update_uid(&mut self, new_uid: i64) -> Result<()>;
+
+    fn rollback_balance_history(&mut self, block_uid: i64) -> Result<Vec<DeletedBalanceHistory>>;
+
+    //
+    // LEASES
+    //
+
+    fn get_next_leases_uid(&mut self) -> Result<i64>;
+
+    fn insert_leases(&mut self, leases: &Vec<InsertableLease>) -> Result<()>;
+
+    fn update_leases_block_references(&mut self, block_uid: i64) -> Result<()>;
+
+    fn close_leases_superseded_by(&mut self, updates: &Vec<LeaseOverride>) -> Result<()>;
+
+    fn reopen_leases_superseded_by(&mut self, current_superseded_by: &Vec<i64>) -> Result<()>;
+
+    fn set_leases_next_update_uid(&mut self, new_uid: i64) -> Result<()>;
+
+    fn rollback_leases(&mut self, block_uid: i64) -> Result<Vec<DeletedLease>>;
+
+    //
+    // DATA ENTRIES
+    //
+
+    fn get_next_data_entries_uid(&mut self) -> Result<i64>;
+
+    fn insert_data_entries(&mut self, entries: &Vec<InsertableDataEntry>) -> Result<()>;
+
+    fn update_data_entries_block_references(&mut self, block_uid: i64) -> Result<()>;
+
+    fn close_data_entries_superseded_by(&mut self, updates: &Vec<DataEntryOverride>) -> Result<()>;
+
+    fn reopen_data_entries_superseded_by(&mut self, current_superseded_by: &Vec<i64>)
+        -> Result<()>;
+
+    fn set_data_entries_next_update_uid(&mut self, new_uid: i64) -> Result<()>;
+
+    fn rollback_data_entries(&mut self, block_uid: i64) -> Result<Vec<DeletedDataEntry>>;
+
+    //
+    // ALIASES
+    //
+
+    fn get_aliases(&mut self, aliases: &Vec<String>) -> Result<Vec<Alias>>;
+
+    fn insert_aliases(&mut self, aliases: &Vec<Alias>) -> Result<()>;
+
+    fn update_aliases_block_references(&mut self, block_uid: i64) -> Result<()>;
+
+    fn rollback_aliases(&mut self, block_uid: i64) -> Result<()>;
+
+    //
+    // INGEST EVENTS
+    //
+
+    fn insert_ingest_events(&mut self, events: &Vec<InsertableIngestEvent>) -> Result<()>;
+
     //
     // CANDLES
     //
@@ -151,5 +279,17 @@ pub trait RepoOperations {
 
     fn calculate_non_minute_candles(&mut self, ts: NaiveDateTime) -> Result<()>;
 
+    /// Deletes candles after the first exchange of the block, and moves the pairs window back to
+    /// the block. Called before the later blocks are removed.
     fn rollback_candles(&mut self, block_uid: i64) -> Result<()>;
+
+    /// Timestamp of the first exchange since the last minute candle, i.e. not covered by candles
+    fn get_candles_outdated_since(&mut self) -> Result<Option<NaiveDateTime>>;
+
+    /// Rebuilds all pairs from the minute candles of the last 24h
+    fn calculate_pairs(&mut self) -> Result<()>;
+
+    /// Recalculates pairs traded since the block, and the ones whose candles left the 24h window
+    /// since the key block before it. Pairs left without candles in the window are deleted.
+    fn update_pairs(&mut self, block_uid: i64) -> Result<()>;
 }
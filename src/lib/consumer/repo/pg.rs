@@ -7,19 +7,28 @@ use diesel::{
     prelude::*,
     result::Error as DslError,
     sql_query,
-    sql_types::{Array, BigInt, Int8, Timestamp, VarChar},
+    sql_types::{Array, BigInt, Int8, Integer, Timestamp, VarChar},
     Table,
 };
+use itertools::Itertools;
 use std::mem::drop;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
 use std::{collections::HashMap, num::NonZeroU32};
 use super::super::UidHeight;
 use super::{Repo, RepoOperations};
 use crate::consumer::models::candles::interval_in_seconds;
 use crate::consumer::models::{
+    aliases::Alias,
     asset_tickers::{AssetTickerOverride, DeletedAssetTicker, InsertableAssetTicker},
     assets::{AssetOrigin, AssetOverride, AssetUpdate, DeletedAsset},
+    assets_metadata::{AssetMetadataOverride, DeletedAssetMetadata, InsertableAssetMetadata},
+    balance_history::{BalanceHistoryOverride, DeletedBalanceHistory, InsertableBalanceHistory},
     block_microblock::BlockMicroblock,
     candles::intervals::{self, CANDLE_INTERVALS},
+    data_entries::{DataEntryOverride, DeletedDataEntry, InsertableDataEntry},
+    ingest_events::InsertableIngestEvent,
+    leases::{DeletedLease, InsertableLease, LeaseOverride},
     txs::*,
     waves_data::WavesData,
 };
@@ -30,18 +39,34 @@ use crate::tuple_len::TupleLen;
 
 const MAX_UID: i64 = std::i64::MAX - 1;
 const PG_MAX_INSERT_FIELDS_COUNT: usize = 65535;
+// txs is a view over the partitioned txs_N tables, so it can't be updated directly
+const TXS_TABLES_COUNT: usize = 18;
 
 #[derive(Clone)]
 pub struct PgRepo {
     pool: PgAsyncPool,
+    copy_threshold: usize,
+    txs_partition_size: i32,
+    /// heights below it have txs_N partitions, as of the last committed transaction
+    txs_partitions_bound: Arc<AtomicI32>,
 }
 
-pub fn new(pool: PgAsyncPool) -> PgRepo {
-    PgRepo { pool }
+/// Inserts of more than `copy_threshold` rows into one table are written by binary COPY,
+/// txs_N partitions are created by `txs_partition_size` heights.
+pub fn new(pool: PgAsyncPool, copy_threshold: usize, txs_partition_size: i32) -> PgRepo {
+    PgRepo {
+        pool,
+        copy_threshold,
+        txs_partition_size,
+        txs_partitions_bound: Arc::new(AtomicI32::new(0)),
+    }
 }
 
 pub struct PgRepoOperations<'c> {
     pub conn: &'c mut PgConnection,
+    pub copy_threshold: usize,
+    pub txs_partition_size: i32,
+    pub txs_partitions_bound: i32,
 }
 
 #[async_trait]
@@ -54,14 +79,58 @@ impl Repo for PgRepo {
         F: Send + 'static,
         R: Send + 'static,
     {
+        let copy_threshold = self.copy_threshold;
+        let txs_partition_size = self.txs_partition_size;
+        let txs_partitions_bound = self.txs_partitions_bound.clone();
         let connection = self.pool.get().await?;
         connection
-            .interact(|conn| conn.transaction(|conn| f(&mut PgRepoOperations { conn })))
+            .interact(move |conn| {
+                let mut bound = txs_partitions_bound.load(Ordering::Relaxed);
+                let result = conn.transaction(|conn| {
+                    let mut ops = PgRepoOperations {
+                        conn,
+                        copy_threshold,
+                        txs_partition_size,
+                        txs_partitions_bound: bound,
+                    };
+                    let result = f(&mut ops);
+                    bound = ops.txs_partitions_bound;
+                    result
+                });
+                // partitions created by a rolled back transaction don't exist
+                if result.is_ok() {
+                    txs_partitions_bound.fetch_max(bound, Ordering::Relaxed);
+                }
+                result
+            })
             .await
             .map_err(AppError::from)?
     }
 }
 
+/// Inserts rows by chunked INSERTs, or by a single binary `COPY ... FROM STDIN`
+/// when there are more than `copy_threshold` of them.
+///
+/// COPY can't write `DEFAULT`, so insertables written this way treat `None` as NULL,
+/// which is the default of every nullable column anyway.
+macro_rules! bulk_insert {
+    ($ops:expr, $table:ident, $values:expr) => {{
+        let values = $values;
+        if values.len() > $ops.copy_threshold {
+            diesel::copy_from($table::table)
+                .from_insertable(values)
+                .execute($ops.conn)
+                .map(drop)
+        } else {
+            chunked($table::table, values, |chunk| {
+                diesel::insert_into($table::table)
+                    .values(chunk)
+                    .execute($ops.conn)
+            })
+        }
+    }};
+}
+
 impl RepoOperations for PgRepoOperations<'_> {
     //
     // COMMON
@@ -140,6 +209,15 @@ impl RepoOperations for PgRepoOperations<'_> {
             .map_err(build_err_fn("Cannot get total block id"))
     }
 
+    fn get_last_block_id(&mut self) -> Result<Option<String>> {
+        blocks_microblocks::table
+            .select(blocks_microblocks::id)
+            .order(blocks_microblocks::uid.desc())
+            .first(self.conn)
+            .optional()
+            .map_err(build_err_fn("Cannot get last block id"))
+    }
+
     fn insert_blocks_or_microblocks(&mut self, blocks: &Vec<BlockMicroblock>) -> Result<Vec<i64>> {
         diesel::insert_into(blocks_microblocks::table)
             .values(blocks)
@@ -165,11 +243,23 @@ impl RepoOperations for PgRepoOperations<'_> {
             .map_err(build_err_fn("Cannot delete microblocks"))
     }
 
-    fn rollback_blocks_microblocks(&mut self, block_uid: i64) -> Result<()> {
+    fn get_block_uids_after(&mut self, block_uid: i64) -> Result<Vec<i64>> {
+        blocks_microblocks::table
+            .select(blocks_microblocks::uid)
+            .filter(blocks_microblocks::uid.gt(block_uid))
+            .order(blocks_microblocks::uid)
+            .load(self.conn)
+            .map_err(build_err_fn(format!(
+                "Cannot get blocks/microblocks after block_uid {block_uid}"
+            )))
+    }
+
+    fn rollback_blocks_microblocks(&mut self, block_uid: i64) -> Result<Option<i32>> {
         diesel::delete(blocks_microblocks::table)
             .filter(blocks_microblocks::uid.gt(block_uid))
-            .execute(self.conn)
-            .map(drop)
+            .returning(blocks_microblocks::height)
+            .get_results::<i32>(self.conn)
+            .map(|heights| heights.into_iter().max())
             .map_err(build_err_fn("Cannot rollback blocks/microblocks"))
     }
 
@@ -183,6 +273,15 @@ impl RepoOperations for PgRepoOperations<'_> {
             .map_err(build_err_fn("Cannot insert waves data"))
     }
 
+    fn notify(&mut self, channel: &str, payload: &str) -> Result<()> {
+        sql_query("SELECT pg_notify($1, $2)")
+            .bind::<VarChar, _>(channel)
+            .bind::<VarChar, _>(payload)
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn(format!("Cannot notify channel {channel}")))
+    }
+
     //
     // ASSETS
     //
@@ -194,12 +293,8 @@ impl RepoOperations for PgRepoOperations<'_> {
     }
 
     fn insert_asset_updates(&mut self, updates: &Vec<AssetUpdate>) -> Result<()> {
-        chunked(asset_updates::table, updates, |chunk| {
-            diesel::insert_into(asset_updates::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert new asset updates"))
+        bulk_insert!(self, asset_updates, updates)
+            .map_err(build_err_fn("Cannot insert new asset updates"))
     }
 
     fn insert_asset_origins(&mut self, origins: &Vec<AssetOrigin>) -> Result<()> {
@@ -376,97 +471,189 @@ impl RepoOperations for PgRepoOperations<'_> {
             .map_err(build_err_fn("Cannot get next asset tickers update uid"))
     }
 
+    fn get_current_assets_metadata(
+        &mut self,
+        asset_ids: &Vec<String>,
+    ) -> Result<Vec<InsertableAssetMetadata>> {
+        assets_metadata::table
+            .select(assets_metadata::all_columns)
+            .filter(assets_metadata::superseded_by.eq(MAX_UID))
+            .filter(assets_metadata::asset_id.eq_any(asset_ids))
+            .get_results(self.conn)
+            .map_err(build_err_fn("Cannot get current assets metadata"))
+    }
+
+    fn insert_assets_metadata(&mut self, metadata: &Vec<InsertableAssetMetadata>) -> Result<()> {
+        chunked(assets_metadata::table, metadata, |chunk| {
+            diesel::insert_into(assets_metadata::table)
+                .values(chunk)
+                .execute(self.conn)
+        })
+        .map_err(build_err_fn("Cannot insert new assets metadata"))
+    }
+
+    fn rollback_assets_metadata(&mut self, block_uid: i64) -> Result<Vec<DeletedAssetMetadata>> {
+        diesel::delete(assets_metadata::table)
+            .filter(assets_metadata::block_uid.gt(block_uid))
+            .returning((assets_metadata::uid, assets_metadata::asset_id))
+            .get_results(self.conn)
+            .map(|bs| {
+                bs.into_iter()
+                    .map(|(uid, asset_id)| DeletedAssetMetadata { uid, asset_id })
+                    .collect()
+            })
+            .map_err(build_err_fn("Cannot rollback assets_metadata"))
+    }
+
+    fn update_assets_metadata_block_references(&mut self, block_uid: i64) -> Result<()> {
+        diesel::update(assets_metadata::table)
+            .set((assets_metadata::block_uid.eq(block_uid),))
+            .filter(assets_metadata::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn(
+                "Cannot update assets metadata block references",
+            ))
+    }
+
+    fn reopen_assets_metadata_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        sql_query(
+            "UPDATE assets_metadata SET superseded_by = $1 FROM (SELECT UNNEST($2) AS superseded_by) AS current
+            WHERE assets_metadata.superseded_by = current.superseded_by;")
+            .bind::<BigInt, _>(MAX_UID)
+            .bind::<Array<BigInt>, _>(current_superseded_by)
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot reopen assets_metadata superseded_by"))
+    }
+
+    fn close_assets_metadata_superseded_by(
+        &mut self,
+        updates: &Vec<AssetMetadataOverride>,
+    ) -> Result<()> {
+        let (ids, superseded_by_uids): (Vec<&String>, Vec<i64>) = updates
+            .iter()
+            .map(|u| (&u.asset_id, u.superseded_by))
+            .unzip();
+
+        let q = sql_query(
+            "UPDATE assets_metadata
+            SET superseded_by = updates.superseded_by
+            FROM (SELECT UNNEST($1::text[]) as id, UNNEST($2::int8[]) as superseded_by) AS updates
+            WHERE assets_metadata.asset_id = updates.id AND assets_metadata.superseded_by = $3;",
+        )
+        .bind::<Array<VarChar>, _>(ids)
+        .bind::<Array<BigInt>, _>(superseded_by_uids)
+        .bind::<BigInt, _>(MAX_UID);
+
+        q.execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot close assets_metadata superseded_by"))
+    }
+
+    fn set_assets_metadata_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        sql_query(format!(
+            "select setval('assets_metadata_uid_seq', {}, false);",
+            new_uid
+        ))
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot set assets_metadata next update uid"))
+    }
+
+    fn get_next_assets_metadata_uid(&mut self) -> Result<i64> {
+        diesel::select(sql::<BigInt>("nextval('assets_metadata_uid_seq')"))
+            .get_result(self.conn)
+            .map_err(build_err_fn("Cannot get next assets metadata update uid"))
+    }
+
     //
     // TRANSACTIONS
     //
 
     fn update_transactions_references(&mut self, block_uid: i64) -> Result<()> {
-        diesel::update(txs::table)
-            .set((txs::block_uid.eq(block_uid),))
-            .filter(txs::block_uid.gt(block_uid))
+        for n in 1..=TXS_TABLES_COUNT {
+            sql_query(format!(
+                "UPDATE txs_{n} SET block_uid = $1 WHERE block_uid > $1"
+            ))
+            .bind::<BigInt, _>(block_uid)
             .execute(self.conn)
-            .map(drop)
-            .map_err(build_err_fn("Cannot update transactions references"))
+            .map_err(build_err_fn("Cannot update transactions references"))?;
+        }
+        Ok(())
     }
 
     fn rollback_transactions(&mut self, block_uid: i64) -> Result<()> {
-        diesel::delete(txs::table)
-            .filter(txs::block_uid.gt(block_uid))
-            .execute(self.conn)
-            .map(drop)
-            .map_err(build_err_fn("Cannot rollback transactions"))
+        for n in 1..=TXS_TABLES_COUNT {
+            sql_query(format!("DELETE FROM txs_{n} WHERE block_uid > $1"))
+                .bind::<BigInt, _>(block_uid)
+                .execute(self.conn)
+                .map_err(build_err_fn("Cannot rollback transactions"))?;
+        }
+        Ok(())
+    }
+
+    fn create_txs_partitions(&mut self, height: i32) -> Result<()> {
+        let up_to_height = height + self.txs_partition_size;
+        // the catalog is only looked up once the height gets within a partition of the bound
+        if up_to_height < self.txs_partitions_bound {
+            return Ok(());
+        }
+
+        self.txs_partitions_bound = diesel::select(sql::<Integer>(&format!(
+            "create_txs_partitions({up_to_height}, {})",
+            self.txs_partition_size
+        )))
+        .get_result(self.conn)
+        .map_err(build_err_fn(format!(
+            "Cannot create transactions partitions up to height {height}"
+        )))?;
+        Ok(())
+    }
+
+    fn get_max_tx_uid_at_height(&mut self, height: i32) -> Result<Option<i64>> {
+        txs::table
+            .select(diesel::dsl::max(txs::uid))
+            .filter(txs::height.eq(height))
+            .get_result(self.conn)
+            .map_err(build_err_fn(format!(
+                "Cannot get max tx uid at height {height}"
+            )))
     }
 
     fn insert_txs_1(&mut self, txs: Vec<Tx1>) -> Result<()> {
-        chunked(txs_1::table, &txs, |chunk| {
-            diesel::insert_into(txs_1::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Genesis transactions"))
+        bulk_insert!(self, txs_1, &txs).map_err(build_err_fn("Cannot insert Genesis transactions"))
     }
 
     fn insert_txs_2(&mut self, txs: Vec<Tx2>) -> Result<()> {
-        chunked(txs_2::table, &txs, |chunk| {
-            diesel::insert_into(txs_2::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Payment transactions"))
+        bulk_insert!(self, txs_2, &txs).map_err(build_err_fn("Cannot insert Payment transactions"))
     }
 
     fn insert_txs_3(&mut self, txs: Vec<Tx3>) -> Result<()> {
-        chunked(txs_3::table, &txs, |chunk| {
-            diesel::insert_into(txs_3::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Issue transactions"))
+        bulk_insert!(self, txs_3, &txs).map_err(build_err_fn("Cannot insert Issue transactions"))
     }
 
     fn insert_txs_4(&mut self, txs: Vec<Tx4>) -> Result<()> {
-        chunked(txs_4::table, &txs, |chunk| {
-            diesel::insert_into(txs_4::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Transfer transactions"))
+        bulk_insert!(self, txs_4, &txs).map_err(build_err_fn("Cannot insert Transfer transactions"))
     }
 
     fn insert_txs_5(&mut self, txs: Vec<Tx5>) -> Result<()> {
-        chunked(txs_5::table, &txs, |chunk| {
-            diesel::insert_into(txs_5::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Reissue transactions"))
+        bulk_insert!(self, txs_5, &txs).map_err(build_err_fn("Cannot insert Reissue transactions"))
     }
 
     fn insert_txs_6(&mut self, txs: Vec<Tx6>) -> Result<()> {
-        chunked(txs_6::table, &txs, |chunk| {
-            diesel::insert_into(txs_6::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Burn transactions"))
+        bulk_insert!(self, txs_6, &txs).map_err(build_err_fn("Cannot insert Burn transactions"))
     }
 
     fn insert_txs_7(&mut self, txs: Vec<Tx7>) -> Result<()> {
-        chunked(txs_7::table, &txs, |chunk| {
-            diesel::insert_into(txs_7::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Exchange transactions"))
+        bulk_insert!(self, txs_7, &txs).map_err(build_err_fn("Cannot insert Exchange transactions"))
     }
 
     fn insert_txs_8(&mut self, txs: Vec<Tx8>) -> Result<()> {
-        chunked(txs_8::table, &txs, |chunk| {
-            diesel::insert_into(txs_8::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert Lease transactions"))
+        bulk_insert!(self, txs_8, &txs).map_err(build_err_fn("Cannot insert Lease transactions"))
     }
 
     fn insert_txs_9(&mut self, txs: Vec<Tx9Partial>) -> Result<()> {
@@ -496,21 +683,13 @@ impl RepoOperations for PgRepoOperations<'_> {
             })
             .collect::<Vec<_>>();
 
-        chunked(txs_9::table, &txs9, |chunk| {
-            diesel::insert_into(txs_9::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert LeaseCancel transactions"))
+        bulk_insert!(self, txs_9, &txs9)
+            .map_err(build_err_fn("Cannot insert LeaseCancel transactions"))
     }
 
     fn insert_txs_10(&mut self, txs: Vec<Tx10>) -> Result<()> {
-        chunked(txs_10::table, &txs, |chunk| {
-            diesel::insert_into(txs_10::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert CreateAlias transactions"))
+        bulk_insert!(self, txs_10, &txs)
+            .map_err(build_err_fn("Cannot insert CreateAlias transactions"))
     }
 
     fn insert_txs_11(&mut self, txs: Vec<Tx11Combined>) -> Result<()> {
@@ -518,19 +697,11 @@ impl RepoOperations for PgRepoOperations<'_> {
             txs.into_iter().map(|t| (t.tx, t.transfers)).unzip();
         let transfers = transfers.into_iter().flatten().collect::<Vec<_>>();
 
-        chunked(txs_11::table, &txs11, |chunk| {
-            diesel::insert_into(txs_11::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert MassTransfer transactions"))?;
+        bulk_insert!(self, txs_11, &txs11)
+            .map_err(build_err_fn("Cannot insert MassTransfer transactions"))?;
 
-        chunked(txs_11_transfers::table, &transfers, |chunk| {
-            diesel::insert_into(txs_11_transfers::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert MassTransfer transfers"))
+        bulk_insert!(self, txs_11_transfers, &transfers)
+            .map_err(build_err_fn("Cannot insert MassTransfer transfers"))
     }
 
     fn insert_txs_12(&mut self, txs: Vec<Tx12Combined>) -> Result<()> {
@@ -538,46 +709,26 @@ impl RepoOperations for PgRepoOperations<'_> {
             txs.into_iter().map(|t| (t.tx, t.data)).unzip();
         let data = data.into_iter().flatten().collect::<Vec<_>>();
 
-        chunked(txs_12::table, &txs12, |chunk| {
-            diesel::insert_into(txs_12::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert DataTransaction transaction"))?;
+        bulk_insert!(self, txs_12, &txs12)
+            .map_err(build_err_fn("Cannot insert DataTransaction transaction"))?;
 
-        chunked(txs_12_data::table, &data, |chunk| {
-            diesel::insert_into(txs_12_data::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert DataTransaction data"))
+        bulk_insert!(self, txs_12_data, &data)
+            .map_err(build_err_fn("Cannot insert DataTransaction data"))
     }
 
     fn insert_txs_13(&mut self, txs: Vec<Tx13>) -> Result<()> {
-        chunked(txs_13::table, &txs, |chunk| {
-            diesel::insert_into(txs_13::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert SetScript transactions"))
+        bulk_insert!(self, txs_13, &txs)
+            .map_err(build_err_fn("Cannot insert SetScript transactions"))
     }
 
     fn insert_txs_14(&mut self, txs: Vec<Tx14>) -> Result<()> {
-        chunked(txs_14::table, &txs, |chunk| {
-            diesel::insert_into(txs_14::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert SponsorFee transactions"))
+        bulk_insert!(self, txs_14, &txs)
+            .map_err(build_err_fn("Cannot insert SponsorFee transactions"))
     }
 
     fn insert_txs_15(&mut self, txs: Vec<Tx15>) -> Result<()> {
-        chunked(txs_15::table, &txs, |chunk| {
-            diesel::insert_into(txs_15::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert SetAssetScript transactions"))
+        bulk_insert!(self, txs_15, &txs)
+            .map_err(build_err_fn("Cannot insert SetAssetScript transactions"))
     }
 
     fn insert_txs_16(&mut self, txs: Vec<Tx16Combined>) -> Result<()> {
@@ -590,35 +741,19 @@ impl RepoOperations for PgRepoOperations<'_> {
         let args = args.into_iter().flatten().collect::<Vec<_>>();
         let payments = payments.into_iter().flatten().collect::<Vec<_>>();
 
-        chunked(txs_16::table, &txs16, |chunk| {
-            diesel::insert_into(txs_16::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert InvokeScript transactions"))?;
+        bulk_insert!(self, txs_16, &txs16)
+            .map_err(build_err_fn("Cannot insert InvokeScript transactions"))?;
 
-        chunked(txs_16_args::table, &args, |chunk| {
-            diesel::insert_into(txs_16_args::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert InvokeScript args"))?;
+        bulk_insert!(self, txs_16_args, &args)
+            .map_err(build_err_fn("Cannot insert InvokeScript args"))?;
 
-        chunked(txs_16_payment::table, &payments, |chunk| {
-            diesel::insert_into(txs_16_payment::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert InvokeScript payments"))
+        bulk_insert!(self, txs_16_payment, &payments)
+            .map_err(build_err_fn("Cannot insert InvokeScript payments"))
     }
 
     fn insert_txs_17(&mut self, txs: Vec<Tx17>) -> Result<()> {
-        chunked(txs_17::table, &txs, |chunk| {
-            diesel::insert_into(txs_17::table)
-                .values(chunk)
-                .execute(self.conn)
-        })
-        .map_err(build_err_fn("Cannot insert UpdateAssetInfo transactions"))
+        bulk_insert!(self, txs_17, &txs)
+            .map_err(build_err_fn("Cannot insert UpdateAssetInfo transactions"))
     }
 
     fn insert_txs_18(&mut self, txs: Vec<Tx18Combined>) -> Result<()> {
@@ -631,26 +766,362 @@ impl RepoOperations for PgRepoOperations<'_> {
         let args = args.into_iter().flatten().collect::<Vec<_>>();
         let payments = payments.into_iter().flatten().collect::<Vec<_>>();
 
-        chunked(txs_18::table, &txs18, |chunk| {
-            diesel::insert_into(txs_18::table)
+        bulk_insert!(self, txs_18, &txs18)
+            .map_err(build_err_fn("Cannot insert Ethereum transactions"))?;
+
+        bulk_insert!(self, txs_18_args, &args)
+            .map_err(build_err_fn("Cannot insert Ethereum InvokeScript args"))?;
+
+        bulk_insert!(self, txs_18_payment, &payments)
+            .map_err(build_err_fn("Cannot insert Ethereum InvokeScript payments"))
+    }
+
+    //
+    // BALANCES
+    //
+
+    fn get_next_balance_history_uid(&mut self) -> Result<i64> {
+        diesel::select(sql::<BigInt>("nextval('balance_history_uid_seq')"))
+            .get_result(self.conn)
+            .map_err(build_err_fn("Cannot get next balance history uid"))
+    }
+
+    fn insert_balance_history(&mut self, balances: &Vec<InsertableBalanceHistory>) -> Result<()> {
+        chunked(balance_history::table, balances, |chunk| {
+            diesel::insert_into(balance_history::table)
                 .values(chunk)
                 .execute(self.conn)
         })
-        .map_err(build_err_fn("Cannot insert Ethereum transactions"))?;
+        .map_err(build_err_fn("Cannot insert new balance history"))
+    }
+
+    fn update_balance_history_block_references(&mut self, block_uid: i64) -> Result<()> {
+        diesel::update(balance_history::table)
+            .set((balance_history::block_uid.eq(block_uid),))
+            .filter(balance_history::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn(
+                "Cannot update balance history block references",
+            ))
+    }
 
-        chunked(txs_18_args::table, &args, |chunk| {
-            diesel::insert_into(txs_18_args::table)
+    fn close_balance_history_superseded_by(
+        &mut self,
+        updates: &Vec<BalanceHistoryOverride>,
+    ) -> Result<()> {
+        let mut addresses = vec![];
+        let mut asset_ids = vec![];
+        let mut superseded_by_uids = vec![];
+
+        updates.iter().for_each(|u| {
+            addresses.push(&u.address);
+            asset_ids.push(&u.asset_id);
+            superseded_by_uids.push(u.superseded_by);
+        });
+
+        let q = sql_query(
+            "UPDATE balance_history
+            SET superseded_by = updates.superseded_by
+            FROM (SELECT UNNEST($1::text[]) as address, UNNEST($2::text[]) as asset_id, UNNEST($3::int8[]) as superseded_by) AS updates
+            WHERE balance_history.address = updates.address
+                AND balance_history.asset_id = updates.asset_id
+                AND balance_history.superseded_by = $4;",
+        )
+        .bind::<Array<VarChar>, _>(addresses)
+        .bind::<Array<VarChar>, _>(asset_ids)
+        .bind::<Array<BigInt>, _>(superseded_by_uids)
+        .bind::<BigInt, _>(MAX_UID);
+
+        q.execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot close balance history superseded_by"))
+    }
+
+    fn reopen_balance_history_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        sql_query(
+            "UPDATE balance_history
+            SET superseded_by = $1
+            FROM (SELECT UNNEST($2) AS superseded_by) AS current
+            WHERE balance_history.superseded_by = current.superseded_by;",
+        )
+        .bind::<BigInt, _>(MAX_UID)
+        .bind::<Array<BigInt>, _>(current_superseded_by)
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot reopen balance history superseded_by"))
+    }
+
+    fn set_balance_history_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        // 3rd param - is called; in case of true, value'll be incremented before returning
+        sql_query(format!(
+            "select setval('balance_history_uid_seq', {}, false);",
+            new_uid
+        ))
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot set balance history next update uid"))
+    }
+
+    fn rollback_balance_history(&mut self, block_uid: i64) -> Result<Vec<DeletedBalanceHistory>> {
+        diesel::delete(balance_history::table)
+            .filter(balance_history::block_uid.gt(block_uid))
+            .returning((
+                balance_history::uid,
+                balance_history::address,
+                balance_history::asset_id,
+            ))
+            .get_results(self.conn)
+            .map(|bs| {
+                bs.into_iter()
+                    .map(|(uid, address, asset_id)| DeletedBalanceHistory {
+                        uid,
+                        address,
+                        asset_id,
+                    })
+                    .collect()
+            })
+            .map_err(build_err_fn("Cannot rollback balance history"))
+    }
+
+    //
+    // LEASES
+    //
+
+    fn get_next_leases_uid(&mut self) -> Result<i64> {
+        diesel::select(sql::<BigInt>("nextval('leases_uid_seq')"))
+            .get_result(self.conn)
+            .map_err(build_err_fn("Cannot get next leases update uid"))
+    }
+
+    fn insert_leases(&mut self, leases: &Vec<InsertableLease>) -> Result<()> {
+        chunked(leases::table, leases, |chunk| {
+            diesel::insert_into(leases::table)
+                .values(chunk)
+                .execute(self.conn)
+        })
+        .map_err(build_err_fn("Cannot insert new leases"))
+    }
+
+    fn update_leases_block_references(&mut self, block_uid: i64) -> Result<()> {
+        diesel::update(leases::table)
+            .set((leases::block_uid.eq(block_uid),))
+            .filter(leases::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot update leases block references"))
+    }
+
+    fn close_leases_superseded_by(&mut self, updates: &Vec<LeaseOverride>) -> Result<()> {
+        let (ids, superseded_by_uids): (Vec<&String>, Vec<i64>) = updates
+            .iter()
+            .map(|u| (&u.lease_id, u.superseded_by))
+            .unzip();
+
+        let q = sql_query(
+            "UPDATE leases
+            SET superseded_by = updates.superseded_by
+            FROM (SELECT UNNEST($1::text[]) as id, UNNEST($2::int8[]) as superseded_by) AS updates
+            WHERE leases.lease_id = updates.id AND leases.superseded_by = $3;",
+        )
+        .bind::<Array<VarChar>, _>(ids)
+        .bind::<Array<BigInt>, _>(superseded_by_uids)
+        .bind::<BigInt, _>(MAX_UID);
+
+        q.execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot close leases superseded_by"))
+    }
+
+    fn reopen_leases_superseded_by(&mut self, current_superseded_by: &Vec<i64>) -> Result<()> {
+        sql_query(
+            "UPDATE leases
+            SET superseded_by = $1
+            FROM (SELECT UNNEST($2) AS superseded_by) AS current
+            WHERE leases.superseded_by = current.superseded_by;",
+        )
+        .bind::<BigInt, _>(MAX_UID)
+        .bind::<Array<BigInt>, _>(current_superseded_by)
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot reopen leases superseded_by"))
+    }
+
+    fn set_leases_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        // 3rd param - is called; in case of true, value'll be incremented before returning
+        sql_query(format!(
+            "select setval('leases_uid_seq', {}, false);",
+            new_uid
+        ))
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot set leases next update uid"))
+    }
+
+    fn rollback_leases(&mut self, block_uid: i64) -> Result<Vec<DeletedLease>> {
+        diesel::delete(leases::table)
+            .filter(leases::block_uid.gt(block_uid))
+            .returning((leases::uid, leases::lease_id))
+            .get_results(self.conn)
+            .map(|bs| {
+                bs.into_iter()
+                    .map(|(uid, lease_id)| DeletedLease { uid, lease_id })
+                    .collect()
+            })
+            .map_err(build_err_fn("Cannot rollback leases"))
+    }
+
+    //
+    // DATA ENTRIES
+    //
+
+    fn get_next_data_entries_uid(&mut self) -> Result<i64> {
+        diesel::select(sql::<BigInt>("nextval('data_entries_uid_seq')"))
+            .get_result(self.conn)
+            .map_err(build_err_fn("Cannot get next data entries update uid"))
+    }
+
+    fn insert_data_entries(&mut self, entries: &Vec<InsertableDataEntry>) -> Result<()> {
+        chunked(data_entries::table, entries, |chunk| {
+            diesel::insert_into(data_entries::table)
                 .values(chunk)
                 .execute(self.conn)
         })
-        .map_err(build_err_fn("Cannot insert Ethereum InvokeScript args"))?;
+        .map_err(build_err_fn("Cannot insert new data entries"))
+    }
+
+    fn update_data_entries_block_references(&mut self, block_uid: i64) -> Result<()> {
+        diesel::update(data_entries::table)
+            .set((data_entries::block_uid.eq(block_uid),))
+            .filter(data_entries::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot update data entries block references"))
+    }
+
+    fn close_data_entries_superseded_by(&mut self, updates: &Vec<DataEntryOverride>) -> Result<()> {
+        let mut addresses = vec![];
+        let mut keys = vec![];
+        let mut superseded_by_uids = vec![];
+
+        updates.iter().for_each(|u| {
+            addresses.push(&u.address);
+            keys.push(&u.key);
+            superseded_by_uids.push(u.superseded_by);
+        });
+
+        let q = sql_query(
+            "UPDATE data_entries
+            SET superseded_by = updates.superseded_by
+            FROM (SELECT UNNEST($1::text[]) as address, UNNEST($2::text[]) as key, UNNEST($3::int8[]) as superseded_by) AS updates
+            WHERE data_entries.address = updates.address
+                AND data_entries.key = updates.key
+                AND data_entries.superseded_by = $4;",
+        )
+        .bind::<Array<VarChar>, _>(addresses)
+        .bind::<Array<VarChar>, _>(keys)
+        .bind::<Array<BigInt>, _>(superseded_by_uids)
+        .bind::<BigInt, _>(MAX_UID);
+
+        q.execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot close data entries superseded_by"))
+    }
+
+    fn reopen_data_entries_superseded_by(
+        &mut self,
+        current_superseded_by: &Vec<i64>,
+    ) -> Result<()> {
+        sql_query(
+            "UPDATE data_entries
+            SET superseded_by = $1
+            FROM (SELECT UNNEST($2) AS superseded_by) AS current
+            WHERE data_entries.superseded_by = current.superseded_by;",
+        )
+        .bind::<BigInt, _>(MAX_UID)
+        .bind::<Array<BigInt>, _>(current_superseded_by)
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot reopen data entries superseded_by"))
+    }
+
+    fn set_data_entries_next_update_uid(&mut self, new_uid: i64) -> Result<()> {
+        // 3rd param - is called; in case of true, value'll be incremented before returning
+        sql_query(format!(
+            "select setval('data_entries_uid_seq', {}, false);",
+            new_uid
+        ))
+        .execute(self.conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot set data entries next update uid"))
+    }
 
-        chunked(txs_18_payment::table, &payments, |chunk| {
-            diesel::insert_into(txs_18_payment::table)
+    fn rollback_data_entries(&mut self, block_uid: i64) -> Result<Vec<DeletedDataEntry>> {
+        diesel::delete(data_entries::table)
+            .filter(data_entries::block_uid.gt(block_uid))
+            .returning((data_entries::uid, data_entries::address, data_entries::key))
+            .get_results(self.conn)
+            .map(|bs| {
+                bs.into_iter()
+                    .map(|(uid, address, key)| DeletedDataEntry { uid, address, key })
+                    .collect()
+            })
+            .map_err(build_err_fn("Cannot rollback data entries"))
+    }
+
+    //
+    // ALIASES
+    //
+
+    fn get_aliases(&mut self, aliases: &Vec<String>) -> Result<Vec<Alias>> {
+        chunked_with_result(aliases::table, aliases, |chunk| {
+            aliases::table
+                .filter(aliases::alias.eq_any(chunk))
+                .get_results(self.conn)
+        })
+        .map_err(build_err_fn("Cannot get aliases"))
+    }
+
+    fn insert_aliases(&mut self, aliases: &Vec<Alias>) -> Result<()> {
+        chunked(aliases::table, aliases, |chunk| {
+            diesel::insert_into(aliases::table)
+                .values(chunk)
+                .execute(self.conn)
+        })
+        .map_err(build_err_fn("Cannot insert new aliases"))
+    }
+
+    fn update_aliases_block_references(&mut self, block_uid: i64) -> Result<()> {
+        diesel::update(aliases::table)
+            .set((aliases::block_uid.eq(block_uid),))
+            .filter(aliases::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot update aliases block references"))
+    }
+
+    fn rollback_aliases(&mut self, block_uid: i64) -> Result<()> {
+        diesel::delete(aliases::table)
+            .filter(aliases::block_uid.gt(block_uid))
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot rollback aliases"))
+    }
+
+    //
+    // INGEST EVENTS
+    //
+
+    fn insert_ingest_events(&mut self, events: &Vec<InsertableIngestEvent>) -> Result<()> {
+        chunked(ingest_events::table, events, |chunk| {
+            diesel::insert_into(ingest_events::table)
                 .values(chunk)
                 .execute(self.conn)
         })
-        .map_err(build_err_fn("Cannot insert Ethereum InvokeScript payments"))
+        .map_err(build_err_fn("Cannot insert ingest events"))
     }
 
     //
@@ -827,15 +1298,248 @@ impl RepoOperations for PgRepoOperations<'_> {
                 .with_second(0)
                 .and_then(|ts| ts.with_nanosecond(0))
                 .unwrap(),
-            None => return Ok(()),
+            None => return rollback_pairs(self.conn, block_uid, vec![]),
         };
 
-        diesel::delete(candles::table)
+        let deleted = diesel::delete(candles::table)
             .filter(candles::time_start.gt(first_tx7_in_block_ts))
+            .returning((
+                candles::amount_asset_id,
+                candles::price_asset_id,
+                candles::matcher_address,
+            ))
+            .get_results(self.conn)
+            .map_err(build_err_fn("Cannot rollback candles"))?;
+
+        rollback_pairs(self.conn, block_uid, deleted)
+    }
+
+    fn get_candles_outdated_since(&mut self) -> Result<Option<NaiveDateTime>> {
+        let last_minute_candle_start = candles::table
+            .select(diesel::dsl::max(candles::time_start))
+            .filter(candles::interval.eq(intervals::MIN1))
+            .get_result::<Option<NaiveDateTime>>(self.conn)
+            .map_err(build_err_fn("Cannot get last minute candle"))?;
+
+        let mut query = txs_7::table
+            .select(diesel::dsl::min(txs_7::time_stamp))
+            .into_boxed();
+        if let Some(ts) = last_minute_candle_start {
+            query = query.filter(txs_7::time_stamp.ge(ts));
+        }
+        query
+            .get_result::<Option<NaiveDateTime>>(self.conn)
+            .map_err(build_err_fn("Cannot find exchange txs without candles"))
+    }
+
+    fn calculate_pairs(&mut self) -> Result<()> {
+        // pairs are a 24h window over minute candles, ending at the last key block
+        let insert_pairs_query = r#"
+            WITH last_candle_time AS (
+                SELECT date_trunc('minute', max(time_stamp))::timestamp AS time_start
+                FROM blocks_microblocks
+                WHERE time_stamp IS NOT NULL
+            ),
+            stats AS (
+                SELECT
+                    c.amount_asset_id,
+                    c.price_asset_id,
+                    c.matcher_address,
+                    (array_agg(c.open ORDER BY c.time_start)::numeric[])[1] AS first_price,
+                    (array_agg(c.close ORDER BY c.time_start DESC)::numeric[])[1] AS last_price,
+                    sum(c.volume) AS volume,
+                    sum(c.quote_volume) AS quote_volume,
+                    max(c.high) AS high,
+                    min(c.low) AS low,
+                    floor(sum((c.weighted_average_price * c.volume)::numeric)::numeric / sum(c.volume)::numeric)::numeric
+                        AS weighted_average_price,
+                    sum(c.txs_count) AS txs_count
+                FROM candles c, last_candle_time l
+                WHERE c.interval = '1m'
+                AND c.time_start > l.time_start - interval '1 day'
+                GROUP BY c.amount_asset_id, c.price_asset_id, c.matcher_address
+            )
+            INSERT INTO pairs
+            SELECT
+                s.amount_asset_id,
+                s.price_asset_id,
+                s.first_price,
+                s.last_price,
+                s.volume,
+                CASE
+                    WHEN s.amount_asset_id = 'WAVES' THEN s.volume
+                    WHEN s.price_asset_id = 'WAVES' THEN s.quote_volume
+                    ELSE floor(s.volume * w.weighted_average_price)
+                END AS volume_waves,
+                s.quote_volume,
+                s.high,
+                s.low,
+                s.weighted_average_price,
+                s.txs_count,
+                s.matcher_address
+            FROM stats s
+            LEFT JOIN stats w
+                ON w.amount_asset_id = s.amount_asset_id
+                AND w.price_asset_id = 'WAVES'
+                AND w.matcher_address = s.matcher_address;
+        "#;
+
+        diesel::delete(pairs::table)
+            .execute(self.conn)
+            .map_err(build_err_fn("Cannot clear pairs"))?;
+
+        sql_query(insert_pairs_query)
             .execute(self.conn)
             .map(drop)
-            .map_err(build_err_fn("Cannot rollback candles"))
+            .map_err(build_err_fn("Cannot calculate pairs"))
     }
+
+    fn update_pairs(&mut self, block_uid: i64) -> Result<()> {
+        // the window ended at the key block before `block_uid`
+        sql_query(UPDATE_PAIRS_QUERY)
+            .bind::<BigInt, _>(block_uid)
+            .bind::<BigInt, _>(block_uid)
+            .bind::<BigInt, _>(i64::MAX)
+            .bind::<Array<VarChar>, _>(Vec::<String>::new())
+            .bind::<Array<VarChar>, _>(Vec::<String>::new())
+            .bind::<Array<VarChar>, _>(Vec::<String>::new())
+            .execute(self.conn)
+            .map(drop)
+            .map_err(build_err_fn("Cannot update pairs"))
+    }
+}
+
+/// Recalculates pairs traded since the block `$1` or listed in `$4`..`$6`, and the ones whose
+/// candles crossed the 24h window start as the window end moved from the last key block before
+/// `$2` to the last one up to `$3`. Touched pairs without stats in the window are expired.
+const UPDATE_PAIRS_QUERY: &str = r#"
+    WITH window_ends AS (
+        SELECT
+            (SELECT date_trunc('minute', max(time_stamp))::timestamp
+                FROM blocks_microblocks
+                WHERE time_stamp IS NOT NULL AND uid < $2) AS previous,
+            (SELECT date_trunc('minute', max(time_stamp))::timestamp
+                FROM blocks_microblocks
+                WHERE time_stamp IS NOT NULL AND uid <= $3) AS current
+    ),
+    changed AS (
+        SELECT amount_asset_id, price_asset_id, sender AS matcher_address
+        FROM txs_7
+        WHERE block_uid >= $1
+        UNION
+        SELECT * FROM unnest($4::text[], $5::text[], $6::text[])
+        UNION
+        -- candles which left the window, or got back into it, as its end moved
+        SELECT c.amount_asset_id, c.price_asset_id, c.matcher_address
+        FROM candles c, window_ends e
+        WHERE c.interval = '1m'
+        AND c.time_start > least(e.previous, e.current) - interval '1 day'
+        AND c.time_start <= greatest(e.previous, e.current) - interval '1 day'
+    ),
+    touched AS (
+        SELECT * FROM changed
+        UNION
+        -- volume in WAVES is priced by the pair of the amount asset to WAVES
+        SELECT p.amount_asset_id, p.price_asset_id, p.matcher_address
+        FROM pairs p
+        JOIN changed ch
+            ON ch.amount_asset_id = p.amount_asset_id
+            AND ch.price_asset_id = 'WAVES'
+            AND ch.matcher_address = p.matcher_address
+    ),
+    stats AS (
+        SELECT
+            c.amount_asset_id,
+            c.price_asset_id,
+            c.matcher_address,
+            (array_agg(c.open ORDER BY c.time_start)::numeric[])[1] AS first_price,
+            (array_agg(c.close ORDER BY c.time_start DESC)::numeric[])[1] AS last_price,
+            sum(c.volume) AS volume,
+            sum(c.quote_volume) AS quote_volume,
+            max(c.high) AS high,
+            min(c.low) AS low,
+            floor(sum((c.weighted_average_price * c.volume)::numeric)::numeric / sum(c.volume)::numeric)::numeric
+                AS weighted_average_price,
+            sum(c.txs_count) AS txs_count
+        FROM candles c, window_ends e
+        WHERE c.interval = '1m'
+        AND c.time_start > e.current - interval '1 day'
+        AND (c.amount_asset_id, c.matcher_address)
+            IN (SELECT amount_asset_id, matcher_address FROM touched)
+        GROUP BY c.amount_asset_id, c.price_asset_id, c.matcher_address
+    ),
+    expired AS (
+        DELETE FROM pairs p
+        USING touched t
+        WHERE p.amount_asset_id = t.amount_asset_id
+        AND p.price_asset_id = t.price_asset_id
+        AND p.matcher_address = t.matcher_address
+        AND NOT EXISTS (
+            SELECT 1 FROM stats s
+            WHERE s.amount_asset_id = t.amount_asset_id
+            AND s.price_asset_id = t.price_asset_id
+            AND s.matcher_address = t.matcher_address
+        )
+    )
+    INSERT INTO pairs
+    SELECT
+        s.amount_asset_id,
+        s.price_asset_id,
+        s.first_price,
+        s.last_price,
+        s.volume,
+        CASE
+            WHEN s.amount_asset_id = 'WAVES' THEN s.volume
+            WHEN s.price_asset_id = 'WAVES' THEN s.quote_volume
+            ELSE floor(s.volume * w.weighted_average_price)
+        END AS volume_waves,
+        s.quote_volume,
+        s.high,
+        s.low,
+        s.weighted_average_price,
+        s.txs_count,
+        s.matcher_address
+    FROM stats s
+    JOIN touched t
+        ON t.amount_asset_id = s.amount_asset_id
+        AND t.price_asset_id = s.price_asset_id
+        AND t.matcher_address = s.matcher_address
+    LEFT JOIN stats w
+        ON w.amount_asset_id = s.amount_asset_id
+        AND w.price_asset_id = 'WAVES'
+        AND w.matcher_address = s.matcher_address
+    ON CONFLICT (amount_asset_id, price_asset_id, matcher_address) DO UPDATE SET
+        first_price = EXCLUDED.first_price,
+        last_price = EXCLUDED.last_price,
+        volume = EXCLUDED.volume,
+        volume_waves = EXCLUDED.volume_waves,
+        quote_volume = EXCLUDED.quote_volume,
+        high = EXCLUDED.high,
+        low = EXCLUDED.low,
+        weighted_average_price = EXCLUDED.weighted_average_price,
+        txs_count = EXCLUDED.txs_count;
+"#;
+
+/// Moves the pairs window end back from the last key block to the one up to `block_uid`, also
+/// recalculating the pairs of the deleted candles
+fn rollback_pairs(
+    conn: &mut PgConnection,
+    block_uid: i64,
+    deleted_candles: Vec<(String, String, String)>,
+) -> Result<()> {
+    let (amount_asset_ids, price_asset_ids, matcher_addresses): (Vec<_>, Vec<_>, Vec<_>) =
+        deleted_candles.into_iter().multiunzip();
+
+    sql_query(UPDATE_PAIRS_QUERY)
+        .bind::<BigInt, _>(i64::MAX)
+        .bind::<BigInt, _>(i64::MAX)
+        .bind::<BigInt, _>(block_uid)
+        .bind::<Array<VarChar>, _>(amount_asset_ids)
+        .bind::<Array<VarChar>, _>(price_asset_ids)
+        .bind::<Array<VarChar>, _>(matcher_addresses)
+        .execute(conn)
+        .map(drop)
+        .map_err(build_err_fn("Cannot rollback pairs"))
 }
 
 fn chunked_with_result<T, F, V, R>(
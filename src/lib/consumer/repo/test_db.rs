@@ -0,0 +1,57 @@
+//! Scratch Postgres database for tests of `PgRepoOperations`.
+//!
+//! Tests using it are skipped unless `TEST_DATABASE_URL` is set. Migrations are applied once
+//! per run, and every test runs in a transaction which is never committed,
+//! so the database is left as it was.
+
+use diesel::{migration::MigrationSource, pg::Pg, pg::PgConnection, Connection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::sync::{Mutex, Once};
+
+use super::pg::PgRepoOperations;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+pub const TXS_PARTITION_SIZE: i32 = 100000;
+
+// tests insert the same block ids, so they wait for each other instead of conflicting
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+static MIGRATE: Once = Once::new();
+
+/// Runs the test with operations in an uncommitted transaction,
+/// or does nothing when `TEST_DATABASE_URL` is not set
+pub fn with_ops(copy_threshold: usize, test: impl FnOnce(&mut PgRepoOperations)) {
+    let url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return;
+        }
+    };
+    let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    MIGRATE.call_once(|| {
+        let mut conn = PgConnection::establish(&url).unwrap();
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+    });
+
+    let mut conn = PgConnection::establish(&url).unwrap();
+    conn.begin_test_transaction().unwrap();
+    test(&mut PgRepoOperations {
+        conn: &mut conn,
+        copy_threshold,
+        txs_partition_size: TXS_PARTITION_SIZE,
+        txs_partitions_bound: 0,
+    });
+}
+
+/// Reverts and runs again the migration named `<version>_<name>`
+pub fn redo_migration(conn: &mut PgConnection, name: &str) {
+    let migration = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+        .unwrap()
+        .into_iter()
+        .find(|m| m.name().to_string().ends_with(&format!("_{name}")))
+        .unwrap();
+    conn.revert_migration(migration.as_ref()).unwrap();
+    conn.run_migration(migration.as_ref()).unwrap();
+}
@@ -0,0 +1,1745 @@
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Text},
+    QueryableByName, RunQueryDsl,
+};
+use serde_json::json;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use waves_protobuf_schemas::tonic::Status;
+use waves_protobuf_schemas::waves::{
+    events::state_update::{
+        AssetDetails, AssetStateUpdate, BalanceUpdate, DataEntryUpdate, LeaseUpdate,
+    },
+    events::transaction_metadata::{
+        ethereum_metadata::Action as EthAction, EthereumMetadata, EthereumTransferMetadata,
+    },
+    transaction::Data,
+    Amount, CreateAliasTransactionData, DataEntry, IssueTransactionData,
+};
+
+use super::models::versioned::{Versioned, MAX_UID};
+use super::repo::memory::{self, MemoryRepo, MemoryRepoOperations, VersionedTable};
+use super::repo::{pg::PgRepoOperations, test_db, Repo};
+use super::updates_mock::{
+    self as mock, b58, block_at, exchange_tx, issue_tx, MockEvent, MockUpdatesServer,
+};
+use super::*;
+
+const CHAIN_ID: u8 = b'T';
+/// Full profile with the outbox, so squashes and rollbacks are recorded as well
+const FULL_WITH_EVENTS: IngestionProfile = IngestionProfile {
+    events: true,
+    ..IngestionProfile::FULL
+};
+const ASSET_STORAGE_ADDRESS: [u8; 26] = [7; 26];
+
+fn asset_id(n: u8) -> String {
+    into_base58([n; 32])
+}
+
+fn asset_update(n: u8, volume: i64) -> StateUpdate {
+    StateUpdate {
+        assets: vec![AssetStateUpdate {
+            before: None,
+            after: Some(AssetDetails {
+                asset_id: vec![n; 32],
+                issuer: vec![1; 32],
+                decimals: 8,
+                name: format!("asset {}", n),
+                volume,
+                reissuable: true,
+                ..Default::default()
+            }),
+        }],
+        ..Default::default()
+    }
+}
+
+fn ticker_update(n: u8, ticker: &str) -> StateUpdate {
+    StateUpdate {
+        data_entries: vec![DataEntryUpdate {
+            address: ASSET_STORAGE_ADDRESS.to_vec(),
+            data_entry: Some(DataEntry {
+                key: format!("%s%s__assetId2ticker__{}", asset_id(n)),
+                value: Some(Value::StringValue(ticker.to_owned())),
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn balance_update(address: u8, n: u8, amount_before: i64, amount_after: i64) -> BalanceUpdate {
+    BalanceUpdate {
+        address: vec![address; 26],
+        amount_after: Some(Amount {
+            asset_id: vec![n; 32],
+            amount: amount_after,
+        }),
+        amount_before,
+    }
+}
+
+fn lease_update(n: u8, status: LeaseStatus) -> LeaseUpdate {
+    LeaseUpdate {
+        lease_id: vec![n; 32],
+        status_after: status as i32,
+        amount: 1000,
+        sender: vec![1; 32],
+        recipient: vec![3; 26],
+        origin_transaction_id: vec![n; 32],
+    }
+}
+
+fn data_entry_update(address: u8, key: &str, value: i64) -> DataEntryUpdate {
+    DataEntryUpdate {
+        address: vec![address; 26],
+        data_entry: Some(DataEntry {
+            key: key.to_owned(),
+            value: Some(Value::IntValue(value)),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Issue transaction carrying an arbitrary state update
+fn tx(id: &str, state_update: StateUpdate) -> Tx {
+    Tx {
+        id: id.to_owned(),
+        data: SignedTransaction {
+            transaction: Some(Transaction::WavesTransaction(WavesTx {
+                chain_id: CHAIN_ID as i32,
+                sender_public_key: vec![1; 32],
+                timestamp: 1_600_000_000_000,
+                version: 3,
+                data: Some(Data::Issue(IssueTransactionData {
+                    name: "asset".to_owned(),
+                    amount: 1,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            proofs: vec![],
+        },
+        meta: TransactionMetadata {
+            sender_address: vec![2; 26],
+            ..Default::default()
+        },
+        state_update,
+    }
+}
+
+/// Address bytes with the chain id, like the node sends
+fn address(n: u8) -> Vec<u8> {
+    let mut address = vec![1, CHAIN_ID];
+    address.extend([n; 24]);
+    address
+}
+
+/// CreateAlias transaction of the sender
+fn alias_tx(id: &str, alias: &str, sender: u8) -> Tx {
+    let mut alias_tx = tx(id, StateUpdate::default());
+    if let Some(Transaction::WavesTransaction(t)) = &mut alias_tx.data.transaction {
+        t.data = Some(Data::CreateAlias(CreateAliasTransactionData {
+            alias: alias.to_owned(),
+        }));
+    }
+    alias_tx.meta.sender_address = address(sender);
+    alias_tx
+}
+
+/// Ethereum transfer of asset `n` to the address
+fn eth_transfer(id: &str, recipient: u8, n: u8) -> Tx {
+    Tx {
+        id: id.to_owned(),
+        data: SignedTransaction {
+            transaction: Some(Transaction::EthereumTransaction(vec![])),
+            proofs: vec![],
+        },
+        meta: TransactionMetadata {
+            sender_address: vec![2; 26],
+            metadata: Some(Metadata::Ethereum(EthereumMetadata {
+                timestamp: 1_600_000_000_000,
+                fee: 100000,
+                sender_public_key: vec![1; 64],
+                action: Some(EthAction::Transfer(EthereumTransferMetadata {
+                    recipient_address: vec![recipient; 26],
+                    amount: Some(Amount {
+                        asset_id: vec![n; 32],
+                        amount: 1,
+                    }),
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        state_update: StateUpdate::default(),
+    }
+}
+
+fn block(id: &str, height: i32, txs: Vec<Tx>) -> BlockchainUpdate {
+    BlockchainUpdate::Block(BlockMicroblockAppend {
+        id: id.to_owned(),
+        time_stamp: Some(epoch_ms_to_naivedatetime(
+            1_600_000_000_000 + height as i64 * 60_000,
+        )),
+        height,
+        updated_waves_amount: None,
+        txs,
+    })
+}
+
+fn microblock(id: &str, height: i32, txs: Vec<Tx>) -> BlockchainUpdate {
+    BlockchainUpdate::Microblock(BlockMicroblockAppend {
+        id: id.to_owned(),
+        time_stamp: None,
+        height,
+        updated_waves_amount: None,
+        txs,
+    })
+}
+
+fn rollback_to(id: &str) -> BlockchainUpdate {
+    BlockchainUpdate::Rollback(id.to_owned())
+}
+
+fn apply<R: RepoOperations>(
+    repo: &mut R,
+    updates: Vec<BlockchainUpdate>,
+    assets_only: bool,
+) -> HandledCounts {
+    let profile = if assets_only {
+        IngestionProfile::ASSETS_ONLY
+    } else {
+        FULL_WITH_EVENTS
+    };
+    apply_profile(repo, updates, profile, None)
+}
+
+fn apply_profile<R: RepoOperations>(
+    repo: &mut R,
+    updates: Vec<BlockchainUpdate>,
+    profile: IngestionProfile,
+    tx_allow_list: Option<&TxAllowList>,
+) -> HandledCounts {
+    let asset_storage_address = into_base58(ASSET_STORAGE_ADDRESS);
+    handle_updates(
+        BlockchainUpdatesWithLastHeight {
+            last_height: 0,
+            updates,
+        },
+        repo,
+        CHAIN_ID,
+        profile,
+        Some(asset_storage_address.as_str()),
+        &[],
+        tx_allow_list,
+        CandlesMode::Calculate,
+    )
+    .unwrap()
+}
+
+fn block_ids(repo: &MemoryRepoOperations) -> Vec<(&str, i32)> {
+    repo.blocks_microblocks
+        .iter()
+        .map(|b| (b.id.as_str(), b.height))
+        .collect()
+}
+
+fn tx_ids(repo: &MemoryRepoOperations) -> Vec<&str> {
+    repo.txs.iter().map(|t| t.id.as_str()).sorted().collect()
+}
+
+fn volumes(repo: &MemoryRepoOperations, n: u8) -> Vec<i64> {
+    repo.asset_updates
+        .versions(&asset_id(n))
+        .iter()
+        .map(|a| a.volume)
+        .collect()
+}
+
+/// Every version of the key but the last one is superseded by the next one
+fn assert_chained<T: Versioned>(table: &VersionedTable<T>, key: &T::Key) {
+    let versions = table.versions(key);
+    for pair in versions.windows(2) {
+        assert_eq!(pair[0].superseded_by(), pair[1].uid());
+    }
+    assert_eq!(versions.last().unwrap().superseded_by(), MAX_UID);
+}
+
+fn assert_versions_chained(repo: &MemoryRepoOperations, n: u8) {
+    assert_chained(&repo.asset_updates, &asset_id(n));
+}
+
+#[test]
+fn blocks_supersede_asset_versions() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            block("B2", 2, vec![tx("T2", asset_update(1, 200))]),
+        ],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![block("B3", 3, vec![tx("T3", asset_update(1, 300))])],
+        false,
+    );
+
+    assert_eq!(block_ids(&repo), vec![("B1", 1), ("B2", 2), ("B3", 3)]);
+    assert_eq!(volumes(&repo, 1), vec![100, 200, 300]);
+    assert_versions_chained(&repo, 1);
+    assert_eq!(tx_ids(&repo), vec!["T1", "T2", "T3"]);
+
+    let versions = repo.asset_updates.versions(&asset_id(1));
+    let block_uids = versions.iter().map(|a| a.block_uid).collect_vec();
+    assert_eq!(
+        block_uids,
+        repo.blocks_microblocks.iter().map(|b| b.uid).collect_vec()
+    );
+    assert_eq!(repo.asset_origins.len(), 1);
+    assert_eq!(
+        repo.asset_origins[0].first_asset_update_uid,
+        versions[0].uid
+    );
+}
+
+#[test]
+fn balances_leases_and_data_entries_are_versioned() {
+    let mut repo = MemoryRepoOperations::default();
+    let balance = (into_base58([3; 26]), asset_id(1));
+    let lease = into_base58([1; 32]);
+    let entry = (into_base58([3; 26]), "k".to_owned());
+
+    apply(
+        &mut repo,
+        vec![
+            block(
+                "B1",
+                1,
+                vec![
+                    tx(
+                        "T1",
+                        StateUpdate {
+                            balances: vec![balance_update(3, 1, 0, 100)],
+                            individual_leases: vec![lease_update(1, LeaseStatus::Active)],
+                            data_entries: vec![data_entry_update(3, "k", 1)],
+                            ..Default::default()
+                        },
+                    ),
+                    tx(
+                        "T2",
+                        StateUpdate {
+                            balances: vec![balance_update(3, 1, 100, 150)],
+                            data_entries: vec![data_entry_update(3, "k", 2)],
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            microblock(
+                "M1",
+                1,
+                vec![tx(
+                    "T3",
+                    StateUpdate {
+                        balances: vec![balance_update(3, 1, 150, 175)],
+                        individual_leases: vec![lease_update(1, LeaseStatus::Inactive)],
+                        data_entries: vec![data_entry_update(3, "k", 3)],
+                        ..Default::default()
+                    },
+                )],
+            ),
+        ],
+        false,
+    );
+
+    let amounts = |repo: &MemoryRepoOperations| {
+        repo.balance_history
+            .versions(&balance)
+            .iter()
+            .map(|b| b.amount_after)
+            .collect_vec()
+    };
+    assert_eq!(amounts(&repo), vec![100, 150, 175]);
+    assert_chained(&repo.balance_history, &balance);
+    assert_eq!(repo.leases.current(&lease).unwrap().status, "cancelled");
+    assert_chained(&repo.leases, &lease);
+    assert_eq!(repo.data_entries.versions(&entry).len(), 3);
+    assert_chained(&repo.data_entries, &entry);
+
+    apply(&mut repo, vec![rollback_to("B1")], false);
+
+    assert_eq!(amounts(&repo), vec![100, 150]);
+    assert_chained(&repo.balance_history, &balance);
+    assert_eq!(repo.leases.versions(&lease).len(), 1);
+    assert_eq!(repo.leases.current(&lease).unwrap().status, "active");
+    let current_entry = repo.data_entries.current(&entry).unwrap();
+    assert_eq!(current_entry.value_integer, Some(2));
+    assert_chained(&repo.data_entries, &entry);
+}
+
+#[test]
+fn skipped_transaction_leaves_no_balance_history() {
+    let mut repo = MemoryRepoOperations::default();
+    let mut broken = tx(
+        "T1",
+        StateUpdate {
+            balances: vec![balance_update(3, 1, 0, 100)],
+            ..Default::default()
+        },
+    );
+    broken.data.transaction = None;
+
+    apply(&mut repo, vec![block("B1", 1, vec![broken])], false);
+
+    assert!(tx_ids(&repo).is_empty());
+    assert!(repo.balance_history.rows.is_empty());
+}
+
+#[test]
+fn microblocks_are_squashed_into_key_block() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![block("B1", 1, vec![tx("T1", asset_update(1, 100))])],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![microblock("M1", 1, vec![tx("T2", asset_update(1, 150))])],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![microblock("M2", 1, vec![tx("T3", ticker_update(1, "AAA"))])],
+        false,
+    );
+
+    assert_eq!(block_ids(&repo), vec![("B1", 1), ("M1", 1), ("M2", 1)]);
+
+    apply(&mut repo, vec![block("B2", 2, vec![])], false);
+
+    // the key block takes the id of the last microblock
+    assert_eq!(block_ids(&repo), vec![("M2", 1), ("B2", 2)]);
+    let key_block_uid = repo.blocks_microblocks[0].uid;
+
+    assert_eq!(volumes(&repo, 1), vec![100, 150]);
+    assert_versions_chained(&repo, 1);
+    assert!(repo
+        .asset_updates
+        .rows
+        .iter()
+        .all(|a| a.block_uid == key_block_uid));
+    assert!(repo.txs.iter().all(|t| t.block_uid == key_block_uid));
+
+    let ticker = repo.asset_tickers.current(&asset_id(1)).unwrap();
+    assert_eq!(ticker.ticker, "AAA");
+    assert_eq!(ticker.block_uid, key_block_uid);
+}
+
+#[test]
+fn microblocks_in_one_batch_are_squashed() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            microblock("M1", 1, vec![tx("T2", asset_update(1, 150))]),
+            block("B2", 2, vec![tx("T3", asset_update(1, 200))]),
+        ],
+        false,
+    );
+
+    assert_eq!(block_ids(&repo), vec![("M1", 1), ("B2", 2)]);
+    assert_eq!(volumes(&repo, 1), vec![100, 150, 200]);
+    assert_versions_chained(&repo, 1);
+}
+
+#[test]
+fn rollback_to_microblock_reverts_later_microblocks() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            microblock("M1", 1, vec![tx("T2", asset_update(1, 150))]),
+            microblock("M2", 1, vec![tx("T3", asset_update(1, 175))]),
+        ],
+        false,
+    );
+    apply(&mut repo, vec![rollback_to("M1")], false);
+
+    assert_eq!(block_ids(&repo), vec![("B1", 1), ("M1", 1)]);
+    assert_eq!(volumes(&repo, 1), vec![100, 150]);
+    assert_versions_chained(&repo, 1);
+    assert_eq!(tx_ids(&repo), vec!["T1", "T2"]);
+}
+
+#[test]
+fn rollback_to_block_restores_tickers() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", ticker_update(1, "AAA"))]),
+            block("B2", 2, vec![tx("T2", ticker_update(1, "BBB"))]),
+            block("B3", 3, vec![tx("T3", ticker_update(1, "CCC"))]),
+        ],
+        false,
+    );
+    assert_eq!(
+        repo.asset_tickers.current(&asset_id(1)).unwrap().ticker,
+        "CCC"
+    );
+
+    let counts = apply(&mut repo, vec![rollback_to("B1")], false);
+    assert_eq!(counts.rollback_depths, vec![2]);
+
+    assert_eq!(block_ids(&repo), vec![("B1", 1)]);
+    assert_eq!(repo.asset_tickers.versions(&asset_id(1)).len(), 1);
+    assert_eq!(
+        repo.asset_tickers.current(&asset_id(1)).unwrap().ticker,
+        "AAA"
+    );
+    assert_eq!(tx_ids(&repo), vec!["T1"]);
+}
+
+#[test]
+fn fork_is_replaced_after_rollback() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            block(
+                "B2",
+                2,
+                vec![
+                    tx("T2", asset_update(1, 200)),
+                    tx("T3", asset_update(2, 1000)),
+                ],
+            ),
+        ],
+        false,
+    );
+    let counts = apply(
+        &mut repo,
+        vec![
+            rollback_to("B1"),
+            block("B2'", 2, vec![tx("T2'", asset_update(1, 300))]),
+        ],
+        false,
+    );
+    assert_eq!(counts.rollback_depths, vec![1]);
+    assert_eq!(counts.txs.values().sum::<usize>(), 1);
+
+    assert_eq!(block_ids(&repo), vec![("B1", 1), ("B2'", 2)]);
+    assert_eq!(volumes(&repo, 1), vec![100, 300]);
+    assert_versions_chained(&repo, 1);
+    assert!(repo.asset_updates.current(&asset_id(2)).is_none());
+    assert_eq!(tx_ids(&repo), vec!["T1", "T2'"]);
+}
+
+#[test]
+fn assets_only_skips_transactions() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            microblock("M1", 1, vec![tx("T2", asset_update(1, 150))]),
+            block("B2", 2, vec![]),
+        ],
+        true,
+    );
+
+    assert_eq!(block_ids(&repo), vec![("M1", 1), ("B2", 2)]);
+    assert_eq!(volumes(&repo, 1), vec![100, 150]);
+    assert!(repo.txs.is_empty());
+}
+
+#[test]
+fn rows_written_with_another_profile_are_squashed_and_rolled_back() {
+    let mut repo = MemoryRepoOperations::default();
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            microblock("M1", 1, vec![tx("T2", asset_update(1, 150))]),
+        ],
+        false,
+    );
+
+    apply(&mut repo, vec![block("B2", 2, vec![])], true);
+    let key_block_uid = repo.blocks_microblocks[0].uid;
+    assert!(repo.txs.iter().all(|t| t.block_uid == key_block_uid));
+
+    apply(
+        &mut repo,
+        vec![block("B3", 3, vec![tx("T3", asset_update(1, 200))])],
+        false,
+    );
+    apply(&mut repo, vec![rollback_to("B2")], true);
+
+    assert_eq!(block_ids(&repo), vec![("M1", 1), ("B2", 2)]);
+    assert_eq!(tx_ids(&repo), vec!["T1", "T2"]);
+}
+
+#[test]
+fn profile_skips_excluded_tx_types() {
+    let mut repo = MemoryRepoOperations::default();
+    let mut profile = IngestionProfile::FULL;
+    profile.tx_types[3] = false;
+
+    apply_profile(
+        &mut repo,
+        vec![
+            block(
+                "B1",
+                1,
+                vec![tx(
+                    "T1",
+                    StateUpdate {
+                        balances: vec![balance_update(3, 1, 0, 100)],
+                        ..asset_update(1, 100)
+                    },
+                )],
+            ),
+            microblock("M1", 1, vec![tx("T2", asset_update(1, 150))]),
+            block("B2", 2, vec![]),
+        ],
+        profile,
+        None,
+    );
+    apply_profile(&mut repo, vec![rollback_to("M1")], profile, None);
+
+    assert_eq!(volumes(&repo, 1), vec![100, 150]);
+    assert!(repo.txs.is_empty());
+    assert!(repo.balance_history.rows.is_empty());
+}
+
+#[test]
+fn allow_list_keeps_matching_transactions() {
+    let mut repo = MemoryRepoOperations::default();
+    let allow_list = TxAllowList {
+        addresses: HashSet::from([into_base58([2; 26])]),
+        assets: HashSet::new(),
+    };
+    let other_list = TxAllowList {
+        addresses: HashSet::from([into_base58([3; 26])]),
+        assets: HashSet::new(),
+    };
+
+    apply_profile(
+        &mut repo,
+        vec![block(
+            "B1",
+            1,
+            vec![tx(
+                "T1",
+                StateUpdate {
+                    balances: vec![balance_update(3, 1, 0, 100)],
+                    ..asset_update(1, 100)
+                },
+            )],
+        )],
+        IngestionProfile::FULL,
+        Some(&other_list),
+    );
+    apply_profile(
+        &mut repo,
+        vec![block(
+            "B2",
+            2,
+            vec![tx(
+                "T2",
+                StateUpdate {
+                    balances: vec![balance_update(3, 2, 0, 100)],
+                    ..asset_update(2, 100)
+                },
+            )],
+        )],
+        IngestionProfile::FULL,
+        Some(&allow_list),
+    );
+
+    assert_eq!(tx_ids(&repo), vec!["T2"]);
+    let balance_tx_uids = repo.balance_history.rows.iter().map(|b| b.tx_uid);
+    assert!(balance_tx_uids.eq(repo.txs.iter().map(|t| t.uid)));
+    // assets of filtered out transactions are kept for candles
+    assert_eq!(volumes(&repo, 1), vec![100]);
+}
+
+#[test]
+fn allow_list_matches_ethereum_recipient_and_asset() {
+    let mut repo = MemoryRepoOperations::default();
+    let by_recipient = TxAllowList {
+        addresses: HashSet::from([into_base58([4; 26])]),
+        assets: HashSet::new(),
+    };
+    let by_asset = TxAllowList {
+        addresses: HashSet::new(),
+        assets: HashSet::from([asset_id(5)]),
+    };
+
+    apply_profile(
+        &mut repo,
+        vec![block(
+            "B1",
+            1,
+            vec![eth_transfer("T1", 4, 6), eth_transfer("T2", 3, 6)],
+        )],
+        IngestionProfile::FULL,
+        Some(&by_recipient),
+    );
+    apply_profile(
+        &mut repo,
+        vec![block(
+            "B2",
+            2,
+            vec![eth_transfer("T3", 3, 5), eth_transfer("T4", 3, 6)],
+        )],
+        IngestionProfile::FULL,
+        Some(&by_asset),
+    );
+
+    assert_eq!(tx_ids(&repo), vec!["T1", "T3"]);
+}
+
+#[test]
+fn first_alias_creation_wins() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![block(
+            "B1",
+            1,
+            vec![alias_tx("T1", "alice", 3), alias_tx("T2", "alice", 4)],
+        )],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![block(
+            "B2",
+            2,
+            vec![alias_tx("T3", "alice", 5), alias_tx("T4", "bob", 5)],
+        )],
+        false,
+    );
+
+    let aliases = repo
+        .aliases
+        .iter()
+        .map(|a| (a.alias.as_str(), a.address.clone()))
+        .collect_vec();
+    assert_eq!(
+        aliases,
+        vec![
+            ("alice", into_base58(address(3))),
+            ("bob", into_base58(address(5)))
+        ]
+    );
+    assert_eq!(repo.aliases[0].qualified_alias, "alias:T:alice");
+}
+
+#[tokio::test]
+async fn failed_transaction_is_discarded() {
+    let repo = memory::new();
+
+    let result = repo
+        .transaction(|ops| {
+            apply(ops, vec![block("B1", 1, vec![])], false);
+            Err::<(), _>(anyhow::anyhow!("interrupted"))
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert!(repo.snapshot().blocks_microblocks.is_empty());
+}
+
+fn tx_uids(repo: &MemoryRepoOperations) -> Vec<(&str, i64)> {
+    repo.txs
+        .iter()
+        .map(|t| (t.id.as_str(), t.uid))
+        .sorted_by_key(|(_, uid)| *uid)
+        .collect()
+}
+
+#[test]
+fn microblocks_continue_tx_uids_of_height() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![block(
+            "B1",
+            1,
+            vec![
+                tx("T1", StateUpdate::default()),
+                tx("T2", StateUpdate::default()),
+            ],
+        )],
+        false,
+    );
+    // every batch starts with a fresh generator, as after a restart
+    apply(
+        &mut repo,
+        vec![microblock("M1", 1, vec![tx("T3", StateUpdate::default())])],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![
+            microblock("M2", 1, vec![tx("T4", StateUpdate::default())]),
+            block("B2", 2, vec![tx("T5", StateUpdate::default())]),
+        ],
+        false,
+    );
+
+    assert_eq!(
+        tx_uids(&repo),
+        vec![
+            ("T1", 100000),
+            ("T2", 100001),
+            ("T3", 100002),
+            ("T4", 100003),
+            ("T5", 200000)
+        ]
+    );
+}
+
+#[test]
+fn tx_uids_are_reused_after_rollback_to_microblock() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", StateUpdate::default())]),
+            microblock("M1", 1, vec![tx("T2", StateUpdate::default())]),
+            microblock("M2", 1, vec![tx("T3", StateUpdate::default())]),
+        ],
+        false,
+    );
+    apply(
+        &mut repo,
+        vec![
+            rollback_to("M1"),
+            microblock("M2'", 1, vec![tx("T3'", StateUpdate::default())]),
+        ],
+        false,
+    );
+
+    assert_eq!(
+        tx_uids(&repo),
+        vec![("T1", 100000), ("T2", 100001), ("T3'", 100002)]
+    );
+}
+
+#[test]
+fn tx_uid_generator_fails_on_overflow() {
+    let mut ugen = TxUidGenerator::new(2);
+
+    ugen.start_height(3, Some(6)).unwrap();
+    assert_eq!(ugen.next().unwrap(), 7);
+    assert!(ugen.next().is_err());
+
+    // a uid of another height means the stored uids were generated with another multiplier
+    assert!(ugen.start_height(4, Some(6)).is_err());
+}
+
+#[test]
+fn ingest_events_record_appends_and_rollbacks() {
+    let mut repo = MemoryRepoOperations::default();
+
+    apply(
+        &mut repo,
+        vec![
+            block("B1", 1, vec![tx("T1", asset_update(1, 100))]),
+            microblock("M1", 1, vec![tx("T2", ticker_update(1, "ONE"))]),
+        ],
+        false,
+    );
+    apply(&mut repo, vec![rollback_to("B1")], false);
+
+    let events = repo
+        .ingest_events
+        .iter()
+        .map(|e| (e.event_type.as_str(), e.block_uid, e.payload.clone()))
+        .collect_vec();
+    assert_eq!(
+        events,
+        vec![
+            ("asset_updates", 1, json!({ "ids": [asset_id(1)] })),
+            ("transactions", 1, json!({ "ids": ["T1"] })),
+            ("transactions", 2, json!({ "ids": ["T2"] })),
+            ("asset_tickers", 2, json!({ "ids": [asset_id(1)] })),
+            ("rollback", 1, json!({ "block_uids": [2] })),
+        ]
+    );
+    assert!(repo.ingest_events.iter().all(|e| e.height == 1));
+
+    apply(
+        &mut repo,
+        vec![microblock("M2", 1, vec![]), block("B2", 2, vec![])],
+        false,
+    );
+    let squashed = repo.ingest_events.last().unwrap();
+    assert_eq!(squashed.event_type, "microblocks_squashed");
+    assert_eq!(squashed.block_uid, 1);
+    assert_eq!(squashed.payload, json!({ "block_uids": [3] }));
+
+    let mut repo = MemoryRepoOperations::default();
+    apply(
+        &mut repo,
+        vec![block("B1", 1, vec![tx("T1", asset_update(1, 100))])],
+        true,
+    );
+    assert!(repo.ingest_events.is_empty());
+}
+
+/// Consumer run against a local mock node serving the scripts
+struct Scenario {
+    server: MockUpdatesServer,
+    repo: MemoryRepo,
+    config: Config,
+}
+
+impl Scenario {
+    async fn new(scripts: Vec<Vec<MockEvent>>) -> Self {
+        let server = mock::start(scripts).await.unwrap();
+        let config = Config {
+            asset_storage_address: None,
+            profile: IngestionProfile::FULL,
+            blockchain_updates_urls: vec![server.url()],
+            chain_id: CHAIN_ID,
+            max_wait_time: Duration::milliseconds(100),
+            starting_height: 1,
+            target_height: None,
+            updates_per_request: 1,
+            start_rollback_depth: NonZeroU32::new(1).unwrap(),
+            rollback_step: NonZeroU32::new(1).unwrap(),
+            metrics_port: 0,
+            asset_oracle_key_patterns: vec![],
+            reconnect_initial_delay: Duration::milliseconds(10),
+            reconnect_max_delay: Duration::milliseconds(100),
+            updates_stale_timeout: Duration::seconds(30),
+            resubscribe_rollback_depth: NonZeroU32::new(1).unwrap(),
+            updates_file_path: None,
+            record_updates_dir: None,
+            record_max_file_size: 0,
+            backfill_segment_size: None,
+            backfill_concurrency: NonZeroUsize::new(1).unwrap(),
+            copy_threshold: 0,
+            txs_partition_size: NonZeroU32::new(100000).unwrap(),
+            candles_defer_blocks_behind: None,
+            tx_allow_list: None,
+            notify_channel: None,
+        };
+        Scenario {
+            server,
+            repo: memory::new(),
+            config,
+        }
+    }
+
+    /// Runs the consumer until the target height or the stop signal
+    async fn run(&self, stop: impl Future<Output = ()> + Send + 'static) -> Stopped {
+        let source = self.server.connect().await;
+        start(source, self.repo.clone(), self.config.clone(), stop)
+            .await
+            .unwrap()
+    }
+
+    async fn backfill(&self) -> Stopped {
+        let source = self.server.connect().await;
+        backfill::start(
+            source,
+            self.repo.clone(),
+            self.config.clone(),
+            std::future::pending(),
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Resolves once the repo has blocks up to the height
+    fn height_reached(&self, height: i32) -> impl Future<Output = ()> + Send + 'static {
+        let repo = self.repo.clone();
+        async move {
+            while !repo
+                .snapshot()
+                .blocks_microblocks
+                .iter()
+                .any(|b| b.height >= height)
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        }
+    }
+
+    fn block_ids(&self) -> Vec<String> {
+        self.repo
+            .snapshot()
+            .blocks_microblocks
+            .into_iter()
+            .map(|b| b.id)
+            .collect()
+    }
+
+    /// Payloads sent to the "blocks" channel
+    fn notifications(&self) -> Vec<serde_json::Value> {
+        self.repo
+            .snapshot()
+            .notifications
+            .into_iter()
+            .map(|(channel, payload)| {
+                assert_eq!(channel, "blocks");
+                serde_json::from_str(&payload).unwrap()
+            })
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn consumer_resubscribes_after_disconnect() {
+    let scenario = Scenario::new(vec![
+        vec![
+            block_at(1, "B1", vec![issue_tx("T1")]),
+            block_at(2, "B2", vec![issue_tx("T2")]),
+            MockEvent::Disconnect,
+        ],
+        vec![MockEvent::Error(Status::unavailable("node is restarting"))],
+        vec![
+            block_at(2, "B2", vec![issue_tx("T2")]),
+            block_at(3, "B3", vec![issue_tx("T3")]),
+        ],
+    ])
+    .await;
+
+    scenario.run(scenario.height_reached(3)).await;
+
+    assert_eq!(scenario.block_ids(), vec![b58("B1"), b58("B2"), b58("B3")]);
+    assert_eq!(scenario.repo.snapshot().txs.len(), 3);
+    // the last saved block is rolled back before resubscribing
+    assert_eq!(scenario.server.subscriptions(), vec![1, 2, 2]);
+}
+
+#[test]
+fn backoff_grows_until_reset() {
+    let mut backoff = Backoff::new(Duration::milliseconds(10), Duration::milliseconds(30));
+    let delays = (0..4).map(|_| backoff.next_delay().num_milliseconds());
+    assert_eq!(delays.collect_vec(), vec![0, 10, 20, 30]);
+
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::zero());
+}
+
+#[tokio::test]
+async fn streams_closed_right_away_are_resubscribed_with_backoff() {
+    let mut scenario = Scenario::new(vec![
+        vec![MockEvent::Disconnect],
+        vec![MockEvent::Disconnect],
+        vec![MockEvent::Disconnect],
+        vec![block_at(1, "B1", vec![issue_tx("T1")])],
+    ])
+    .await;
+    scenario.config.reconnect_initial_delay = Duration::milliseconds(100);
+    scenario.config.reconnect_max_delay = Duration::seconds(1);
+
+    let started = Instant::now();
+    scenario.run(scenario.height_reached(1)).await;
+
+    // the first resubscription is immediate, then 100ms and 200ms
+    assert!(started.elapsed() >= std::time::Duration::from_millis(300));
+    assert_eq!(scenario.server.subscriptions(), vec![1, 1, 1, 1]);
+}
+
+#[tokio::test]
+async fn unreadable_updates_file_stops_the_consumer() {
+    let scenario = Scenario::new(vec![]).await;
+    let source = updates_file::new(std::env::temp_dir().join("missing_updates.pb")).unwrap();
+
+    let result = start(
+        source,
+        scenario.repo.clone(),
+        scenario.config.clone(),
+        std::future::pending(),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(scenario.block_ids().is_empty());
+}
+
+#[tokio::test]
+async fn malformed_update_stops_the_consumer() {
+    let mut malformed = mock::block(2, "B2", 1_600_000_120_000, vec![]);
+    malformed.update = None;
+    let scenario = Scenario::new(vec![vec![
+        block_at(1, "B1", vec![issue_tx("T1")]),
+        MockEvent::Update(malformed),
+    ]])
+    .await;
+
+    let source = scenario.server.connect().await;
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        start(
+            source,
+            scenario.repo.clone(),
+            scenario.config.clone(),
+            std::future::pending(),
+        ),
+    )
+    .await
+    .expect("the consumer should stop instead of resubscribing");
+
+    assert!(result.is_err());
+    assert_eq!(scenario.server.subscriptions(), vec![1]);
+}
+
+#[tokio::test]
+async fn bounded_sync_stops_at_target_height() {
+    let mut scenario = Scenario::new(vec![vec![
+        block_at(1, "B1", vec![issue_tx("T1")]),
+        block_at(2, "B2", vec![issue_tx("T2")]),
+        MockEvent::Update(mock::microblock(2, "M1", vec![issue_tx("T3")])),
+        block_at(3, "B3", vec![issue_tx("T4")]),
+    ]])
+    .await;
+    scenario.config.target_height = Some(2);
+    scenario.config.updates_per_request = 10;
+
+    let summary = match scenario.run(std::future::pending()).await {
+        Stopped::TargetHeightReached(summary) => summary,
+        stopped => panic!("unexpected stop {:?}", stopped),
+    };
+    assert_eq!((summary.from_height, summary.to_height), (1, 2));
+    assert_eq!((summary.blocks, summary.microblocks), (2, 1));
+    assert_eq!(summary.transactions, 3);
+
+    // the microblock is squashed into the last block
+    assert_eq!(scenario.block_ids(), vec![b58("B1"), b58("M1")]);
+    assert_eq!(scenario.repo.snapshot().txs.len(), 3);
+    assert_eq!(scenario.server.subscriptions(), vec![1]);
+}
+
+#[tokio::test]
+async fn committed_batches_are_notified() {
+    let mut scenario = Scenario::new(vec![vec![
+        block_at(1, "B1", vec![issue_tx("T1")]),
+        block_at(2, "B2", vec![issue_tx("T2")]),
+        MockEvent::Update(mock::microblock(2, "M1", vec![issue_tx("T3")])),
+        block_at(3, "B3", vec![issue_tx("T4")]),
+    ]])
+    .await;
+    scenario.config.target_height = Some(2);
+    scenario.config.updates_per_request = 10;
+    scenario.config.notify_channel = Some("blocks".to_owned());
+
+    scenario.run(std::future::pending()).await;
+
+    let payloads = scenario.notifications();
+    let issued = payloads
+        .iter()
+        .map(|p| p["tx_counts"]["issue"].as_u64().unwrap_or(0))
+        .sum::<u64>();
+    assert_eq!(issued, 3);
+
+    let [.., last_batch, finished] = &payloads[..] else {
+        panic!("unexpected notifications {:?}", payloads);
+    };
+    assert_eq!(last_batch["height"], 2);
+    assert_eq!(last_batch["block_id"], b58("M1"));
+    assert_eq!(last_batch["microblock"], true);
+    assert_eq!(last_batch["rollback"], false);
+    // the microblock is squashed once the sync is finished
+    assert_eq!(
+        finished,
+        &json!({
+            "height": 2,
+            "block_id": b58("M1"),
+            "microblock": false,
+            "rollback": false,
+            "tx_counts": {},
+        })
+    );
+}
+
+#[tokio::test]
+async fn rollbacks_before_subscribing_are_notified() {
+    let mut scenario = Scenario::new(vec![
+        vec![
+            block_at(1, "B1", vec![]),
+            block_at(2, "B2", vec![]),
+            MockEvent::Disconnect,
+        ],
+        vec![block_at(2, "B2", vec![]), block_at(3, "B3", vec![])],
+        vec![block_at(3, "B3", vec![])],
+    ])
+    .await;
+    scenario.config.notify_channel = Some("blocks".to_owned());
+
+    // rolled back before resubscribing
+    scenario.run(scenario.height_reached(3)).await;
+    // and on start
+    scenario.run(scenario.height_reached(3)).await;
+
+    let rollbacks = scenario
+        .notifications()
+        .into_iter()
+        .filter(|p| p["rollback"] == true)
+        .collect_vec();
+    let rolled_back_to = |height: u32, id: &str| {
+        json!({
+            "height": height,
+            "block_id": b58(id),
+            "microblock": false,
+            "rollback": true,
+            "tx_counts": {},
+        })
+    };
+    assert_eq!(
+        rollbacks,
+        vec![rolled_back_to(1, "B1"), rolled_back_to(2, "B2")]
+    );
+}
+
+#[tokio::test]
+async fn rollbacks_keep_candles_without_the_candles_profile() {
+    let exchange_at = |height: i64| 1_600_000_000_000 + height * 60_000;
+    let mut scenario = Scenario::new(vec![
+        vec![
+            block_at(1, "B1", vec![exchange_tx("E1", exchange_at(1))]),
+            block_at(2, "B2", vec![exchange_tx("E2", exchange_at(2))]),
+        ],
+        vec![block_at(2, "B2", vec![])],
+    ])
+    .await;
+    scenario.run(scenario.height_reached(2)).await;
+    let minute_candles = scenario.repo.snapshot().minute_candles;
+    assert_eq!(minute_candles.len(), 2);
+
+    // rolled back to B1 on start
+    scenario.config.profile = IngestionProfile::ASSETS_ONLY;
+    scenario.run(async {}).await;
+
+    assert_eq!(scenario.block_ids(), vec![b58("B1")]);
+    assert_eq!(scenario.repo.snapshot().minute_candles, minute_candles);
+}
+
+#[tokio::test]
+async fn deferred_candles_are_rebuilt_at_the_tip() {
+    let exchange_at = |height: i64| 1_600_000_000_000 + height * 60_000;
+    let mut scenario = Scenario::new(vec![
+        vec![
+            block_at(1, "B1", vec![exchange_tx("E1", exchange_at(1))]),
+            block_at(2, "B2", vec![exchange_tx("E2", exchange_at(2))]),
+        ],
+        vec![
+            block_at(2, "B2", vec![exchange_tx("E2", exchange_at(2))]),
+            MockEvent::Update(mock::block(3, "B3", Utc::now().timestamp_millis(), vec![])),
+        ],
+    ])
+    .await;
+    scenario.config.candles_defer_blocks_behind = Some(10);
+
+    scenario.run(scenario.height_reached(2)).await;
+    assert!(scenario.repo.snapshot().minute_candles.is_empty());
+
+    scenario.run(scenario.height_reached(3)).await;
+    let minute_candles = scenario.repo.snapshot().minute_candles;
+    assert_eq!(
+        minute_candles.into_iter().collect_vec(),
+        vec![
+            epoch_ms_to_naivedatetime(exchange_at(1) / 60_000 * 60_000),
+            epoch_ms_to_naivedatetime(exchange_at(2) / 60_000 * 60_000),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn deferred_candles_are_rebuilt_when_bounded_sync_finishes() {
+    let exchange_at = |height: i64| 1_600_000_000_000 + height * 60_000;
+    let mut scenario = Scenario::new(vec![vec![
+        block_at(1, "B1", vec![exchange_tx("E1", exchange_at(1))]),
+        block_at(2, "B2", vec![exchange_tx("E2", exchange_at(2))]),
+        block_at(3, "B3", vec![exchange_tx("E3", exchange_at(3))]),
+    ]])
+    .await;
+    scenario.config.target_height = Some(3);
+    scenario.config.updates_per_request = 1;
+    scenario.config.candles_defer_blocks_behind = Some(10);
+
+    match scenario.run(std::future::pending()).await {
+        Stopped::TargetHeightReached(summary) => assert_eq!(summary.to_height, 3),
+        stopped => panic!("unexpected stop {:?}", stopped),
+    }
+
+    let minute_candles = scenario.repo.snapshot().minute_candles;
+    assert_eq!(
+        minute_candles.into_iter().collect_vec(),
+        (1..=3)
+            .map(|height| epoch_ms_to_naivedatetime(exchange_at(height) / 60_000 * 60_000))
+            .collect_vec()
+    );
+}
+
+#[tokio::test]
+async fn backfill_writes_segments_in_height_order() {
+    let mut scenario = Scenario::new(vec![
+        vec![
+            block_at(1, "B1", vec![issue_tx("T1")]),
+            block_at(2, "B2", vec![issue_tx("T2")]),
+            block_at(3, "B3", vec![]),
+        ],
+        vec![block_at(3, "B3", vec![issue_tx("T3")])],
+    ])
+    .await;
+    scenario.config.target_height = Some(3);
+    scenario.config.backfill_segment_size = NonZeroU32::new(2);
+    scenario.config.updates_per_request = 10;
+    scenario.config.notify_channel = Some("blocks".to_owned());
+
+    match scenario.backfill().await {
+        Stopped::TargetHeightReached(summary) => {
+            assert_eq!((summary.from_height, summary.to_height), (1, 3));
+            assert_eq!(summary.blocks, 3);
+        }
+        stopped => panic!("unexpected stop {:?}", stopped),
+    }
+
+    assert_eq!(scenario.block_ids(), vec![b58("B1"), b58("B2"), b58("B3")]);
+    assert_eq!(scenario.repo.snapshot().txs.len(), 3);
+    assert_eq!(scenario.server.subscriptions(), vec![1, 3]);
+
+    let payloads = scenario.notifications();
+    let issued = payloads
+        .iter()
+        .map(|p| p["tx_counts"]["issue"].as_u64().unwrap_or(0))
+        .sum::<u64>();
+    assert_eq!(issued, 3);
+    assert_eq!(payloads.last().unwrap()["block_id"], b58("B3"));
+}
+
+#[derive(QueryableByName)]
+struct PgVersion {
+    #[diesel(sql_type = Text)]
+    key: String,
+    #[diesel(sql_type = Text)]
+    value: String,
+    #[diesel(sql_type = BigInt)]
+    uid: i64,
+    #[diesel(sql_type = BigInt)]
+    superseded_by: i64,
+    #[diesel(sql_type = Text)]
+    block_id: String,
+}
+
+/// `(value, block id)` of all versions in the table by uid,
+/// checking that every version of a key is superseded by the next one
+fn pg_versions(
+    ops: &mut PgRepoOperations,
+    table: &str,
+    key: &str,
+    value: &str,
+) -> Vec<(String, String)> {
+    let versions = sql_query(format!(
+        "SELECT {key} AS key, {value} AS value, t.uid, t.superseded_by, b.id AS block_id
+         FROM {table} t JOIN blocks_microblocks b ON b.uid = t.block_uid
+         ORDER BY t.uid"
+    ))
+    .load::<PgVersion>(ops.conn)
+    .unwrap();
+
+    for (_, versions) in versions.iter().into_group_map_by(|v| v.key.as_str()) {
+        for pair in versions.windows(2) {
+            assert_eq!(pair[0].superseded_by, pair[1].uid);
+        }
+        assert_eq!(versions.last().unwrap().superseded_by, MAX_UID);
+    }
+    versions
+        .into_iter()
+        .map(|v| (v.value, v.block_id))
+        .collect()
+}
+
+fn pg_column(ops: &mut PgRepoOperations, query: &str) -> Vec<String> {
+    #[derive(QueryableByName)]
+    struct Row {
+        #[diesel(sql_type = Text)]
+        value: String,
+    }
+
+    sql_query(query)
+        .load::<Row>(ops.conn)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.value)
+        .collect()
+}
+
+fn owned(rows: &[(&str, &str)]) -> Vec<(String, String)> {
+    rows.iter()
+        .map(|(value, block_id)| (value.to_string(), block_id.to_string()))
+        .collect()
+}
+
+#[test]
+fn pg_versioned_tables_follow_squashes_and_rollbacks() {
+    test_db::with_ops(0, |ops| {
+        apply(
+            ops,
+            vec![
+                block(
+                    "B1",
+                    1,
+                    vec![tx(
+                        "T1",
+                        StateUpdate {
+                            balances: vec![balance_update(3, 1, 0, 100)],
+                            individual_leases: vec![lease_update(1, LeaseStatus::Active)],
+                            data_entries: vec![data_entry_update(3, "k", 1)],
+                            ..asset_update(1, 100)
+                        },
+                    )],
+                ),
+                microblock(
+                    "M1",
+                    1,
+                    vec![tx(
+                        "T2",
+                        StateUpdate {
+                            balances: vec![balance_update(3, 1, 100, 150)],
+                            data_entries: vec![data_entry_update(3, "k", 2)],
+                            ..asset_update(1, 150)
+                        },
+                    )],
+                ),
+            ],
+            false,
+        );
+        apply(
+            ops,
+            vec![microblock(
+                "M2",
+                1,
+                vec![tx(
+                    "T3",
+                    StateUpdate {
+                        balances: vec![balance_update(3, 1, 150, 175)],
+                        individual_leases: vec![lease_update(1, LeaseStatus::Inactive)],
+                        ..asset_update(1, 175)
+                    },
+                )],
+            )],
+            false,
+        );
+        apply(ops, vec![rollback_to("M1")], false);
+        apply(
+            ops,
+            vec![block(
+                "B2",
+                2,
+                vec![tx(
+                    "T4",
+                    StateUpdate {
+                        balances: vec![balance_update(3, 1, 150, 200)],
+                        ..ticker_update(1, "AAA")
+                    },
+                )],
+            )],
+            false,
+        );
+
+        // M2 is rolled back, and M1 is squashed into B1 which takes its id
+        assert_eq!(
+            pg_versions(ops, "asset_updates", "t.asset_id", "t.volume::text"),
+            owned(&[("100", "M1"), ("150", "M1")])
+        );
+        assert_eq!(
+            pg_versions(
+                ops,
+                "balance_history",
+                "t.address || '/' || t.asset_id",
+                "t.amount_after::text"
+            ),
+            owned(&[("100", "M1"), ("150", "M1"), ("200", "B2")])
+        );
+        assert_eq!(
+            pg_versions(ops, "leases", "t.lease_id", "t.status"),
+            owned(&[("active", "M1")])
+        );
+        assert_eq!(
+            pg_versions(
+                ops,
+                "data_entries",
+                "t.address || '/' || t.key",
+                "coalesce(t.value_integer::text, t.value_string)"
+            ),
+            owned(&[("1", "M1"), ("2", "M1"), ("AAA", "B2")])
+        );
+        assert_eq!(
+            pg_versions(ops, "asset_tickers", "t.asset_id", "t.ticker"),
+            owned(&[("AAA", "B2")])
+        );
+
+        assert_eq!(
+            pg_column(ops, "SELECT id AS value FROM txs ORDER BY uid"),
+            vec!["T1", "T2", "T4"]
+        );
+        assert_eq!(
+            pg_column(
+                ops,
+                "SELECT event_type AS value FROM ingest_events ORDER BY seq"
+            ),
+            vec![
+                "asset_updates",
+                "transactions",
+                "asset_updates",
+                "transactions",
+                "asset_updates",
+                "transactions",
+                "rollback",
+                "microblocks_squashed",
+                "transactions",
+                "asset_tickers",
+            ]
+        );
+    });
+}
+
+#[test]
+fn pg_transactions_are_written_to_created_partitions() {
+    test_db::with_ops(0, |ops| {
+        let height = test_db::TXS_PARTITION_SIZE * 2 + 1;
+        apply(
+            ops,
+            vec![block("B1", height, vec![tx("T1", StateUpdate::default())])],
+            false,
+        );
+
+        assert_eq!(
+            pg_column(ops, "SELECT tableoid::regclass::text AS value FROM txs_3"),
+            vec![format!("txs_3_{}", test_db::TXS_PARTITION_SIZE * 2)]
+        );
+        assert_eq!(
+            ops.get_max_tx_uid_at_height(height).unwrap(),
+            Some(height as i64 * TX_UID_MULTIPLIER)
+        );
+    });
+}
+
+#[test]
+fn pg_partitions_are_created_once_the_height_nears_the_bound() {
+    test_db::with_ops(0, |ops| {
+        let size = test_db::TXS_PARTITION_SIZE;
+        let query = "SELECT count(*)::text AS value FROM pg_inherits
+                     WHERE inhparent = 'txs_3'::regclass";
+        ops.create_txs_partitions(1).unwrap();
+        let bound = ops.txs_partitions_bound;
+        assert!(bound > size + 1);
+        let partitions = pg_column(ops, query);
+
+        ops.create_txs_partitions(bound - size - 1).unwrap();
+        assert_eq!(ops.txs_partitions_bound, bound);
+        assert_eq!(pg_column(ops, query), partitions);
+
+        ops.create_txs_partitions(bound - size).unwrap();
+        assert_eq!(ops.txs_partitions_bound, bound + size);
+        assert_ne!(pg_column(ops, query), partitions);
+    });
+}
+
+#[test]
+fn pg_partitions_migration_is_reverted_and_applied_with_rows() {
+    test_db::with_ops(0, |ops| {
+        apply(
+            ops,
+            vec![block("B1", 1, vec![tx("T1", StateUpdate::default())])],
+            false,
+        );
+
+        test_db::redo_migration(ops.conn, "partition_txs");
+
+        assert_eq!(
+            pg_column(ops, "SELECT tableoid::regclass::text AS value FROM txs_3"),
+            vec!["txs_3_0"]
+        );
+        assert_eq!(pg_column(ops, "SELECT id AS value FROM txs"), vec!["T1"]);
+        // the bound constraints only spare attaching from scanning the rows
+        let query = "SELECT conname::text AS value FROM pg_constraint WHERE conname LIKE '%_bound'";
+        assert!(pg_column(ops, query).is_empty());
+        // unique indexes are kept on the partitioned tables, with height
+        let query = "SELECT indexdef AS value FROM pg_indexes
+                     WHERE tablename = 'txs_9' AND indexdef LIKE 'CREATE UNIQUE%' ORDER BY 1";
+        assert_eq!(
+            pg_column(ops, query),
+            vec![
+                "CREATE UNIQUE INDEX txs_9_pk_uid_height ON ONLY public.txs_9 USING btree (uid, height)",
+                "CREATE UNIQUE INDEX txs_9_uid_time_stamp_unique_idx ON ONLY public.txs_9 USING btree (uid, time_stamp, height)",
+                "CREATE UNIQUE INDEX txs_9_un ON ONLY public.txs_9 USING btree (uid, lease_tx_uid, height)",
+            ]
+        );
+        // so that references to transactions are checked
+        let query = "SELECT table_name::text AS value FROM information_schema.columns
+                     WHERE table_name LIKE 'txs_%' AND column_name = 'height' AND is_nullable = 'YES'";
+        assert!(pg_column(ops, query).is_empty());
+    });
+}
+
+#[test]
+fn pg_aliases_migration_seeds_from_transactions() {
+    test_db::with_ops(0, |ops| {
+        apply(
+            ops,
+            vec![block(
+                "B1",
+                1,
+                vec![
+                    alias_tx("T1", "alice", 3),
+                    alias_tx("T2", "alice", 4),
+                    alias_tx("T3", "bob", 5),
+                ],
+            )],
+            false,
+        );
+        let query = "SELECT concat_ws(' ', qualified_alias, address, tx_uid, block_uid) AS value
+                     FROM aliases ORDER BY alias";
+        let written = pg_column(ops, query);
+        assert_eq!(written.len(), 2);
+
+        test_db::redo_migration(ops.conn, "aliases");
+
+        assert_eq!(pg_column(ops, query), written);
+    });
+}
+
+#[test]
+fn pg_pairs_are_updated_as_candles_leave_the_window() {
+    test_db::with_ops(0, |ops| {
+        apply(ops, vec![block("B1", 1, vec![])], false);
+        // blocks are a minute apart, so the candles are at the minutes of heights 1 and 2
+        for (height, price_asset_id) in [(1, "Y"), (2, "WAVES")] {
+            sql_query(format!(
+                "INSERT INTO candles (time_start, amount_asset_id, price_asset_id, low, high,
+                    volume, quote_volume, max_height, txs_count, weighted_average_price,
+                    open, close, interval, matcher_address)
+                 VALUES (
+                    date_trunc('minute', 'epoch'::timestamp
+                        + interval '1 second' * (1600000000 + 60 * {height})),
+                    'X', '{price_asset_id}', 2, 2, 10, 20, {height}, 1, 2, 2, 2, '1m', 'm')"
+            ))
+            .execute(ops.conn)
+            .unwrap();
+        }
+        ops.calculate_pairs().unwrap();
+        // not traded since, so it is left as it is
+        sql_query("INSERT INTO pairs VALUES ('C', 'D', 1, 1, 1, 1, 1, 1, 1, 1, 1, 'm')")
+            .execute(ops.conn)
+            .unwrap();
+        let query = "SELECT concat_ws(' ', amount_asset_id, price_asset_id, volume_waves) AS value
+                     FROM pairs ORDER BY amount_asset_id, price_asset_id";
+        assert_eq!(pg_column(ops, query), vec!["C D 1", "X WAVES 20", "X Y 20"]);
+
+        apply(ops, vec![block("B2", 1 + 24 * 60, vec![])], false);
+        assert_eq!(pg_column(ops, query), vec!["C D 1", "X WAVES 20"]);
+
+        apply(ops, vec![block("B3", 2 + 24 * 60, vec![])], false);
+        assert_eq!(pg_column(ops, query), vec!["C D 1"]);
+
+        // the window end moves back, so the candle of height 2 is in it again
+        apply(ops, vec![rollback_to("B2")], false);
+        assert_eq!(pg_column(ops, query), vec!["C D 1", "X WAVES 20"]);
+    });
+}
+
+#[test]
+fn pg_candles_are_outdated_since_the_last_minute_candle() {
+    test_db::with_ops(0, |ops| {
+        apply(ops, vec![block("B1", 1, vec![])], false);
+        assert_eq!(ops.get_candles_outdated_since().unwrap(), None);
+
+        // exchanges at the 40th second of two consecutive minutes
+        sql_query(
+            "INSERT INTO txs_7 (uid, tx_type, sender, sender_public_key, time_stamp, height, id,
+                fee, block_uid, order1, order2, amount, price, amount_asset_id, price_asset_id,
+                buy_matcher_fee, sell_matcher_fee, fee_asset_id, tx_version)
+             SELECT h, 7, 'm', 'pk', to_timestamp(1600000000 + 60 * h), h, 'E' || h,
+                1, (SELECT max(uid) FROM blocks_microblocks), '{}', '{}', 10, 2, 'X', 'WAVES',
+                0, 0, 'WAVES', 2
+             FROM generate_series(1, 2) h",
+        )
+        .execute(ops.conn)
+        .unwrap();
+        let exchange_ts = |h: i64| epoch_ms_to_naivedatetime(1_600_000_000_000 + h * 60_000);
+        assert_eq!(
+            ops.get_candles_outdated_since().unwrap(),
+            Some(exchange_ts(1))
+        );
+
+        ops.calculate_minute_candles(exchange_ts(1).with_second(0).unwrap())
+            .unwrap();
+        // the last minute may get more exchanges, so it is calculated again
+        assert_eq!(
+            ops.get_candles_outdated_since().unwrap(),
+            Some(exchange_ts(2))
+        );
+    });
+}
+
+#[test]
+fn pg_copy_writes_the_same_rows_as_insert() {
+    let tables = (1..=18)
+        .map(|n| (format!("txs_{n}"), "{block_uid}"))
+        // asset update uids come from a sequence, which isn't rolled back with the transaction
+        .chain([("asset_updates".to_owned(), "{block_uid,uid}")]);
+    let mut written = vec![];
+
+    for copy_threshold in [0, usize::MAX] {
+        test_db::with_ops(copy_threshold, |ops| {
+            apply(
+                ops,
+                vec![block(
+                    "B1",
+                    1,
+                    vec![
+                        tx("T1", asset_update(1, 100)),
+                        alias_tx("T2", "alice", 3),
+                        eth_transfer("T3", 4, 1),
+                    ],
+                )],
+                false,
+            );
+            let rows = tables.clone().flat_map(|(table, generated)| {
+                pg_column(
+                    ops,
+                    &format!(
+                        "SELECT (to_jsonb(t) - '{generated}'::text[])::text AS value
+                         FROM {table} t ORDER BY t.uid"
+                    ),
+                )
+            });
+            written.push(rows.collect_vec());
+        });
+    }
+
+    if let [copied, inserted] = &written[..] {
+        assert_eq!(copied.len(), 4);
+        assert_eq!(copied, inserted);
+    }
+}
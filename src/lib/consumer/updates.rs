@@ -3,6 +3,10 @@ use async_trait::async_trait;
 use bs58;
 use chrono::Duration;
 use std::str;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 use std::time::{Duration as StdDuration, Instant};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::time;
@@ -24,27 +28,57 @@ use waves_protobuf_schemas::waves::{
     Block as BlockPB, SignedMicroBlock as SignedMicroBlockPB,
     SignedTransaction as SignedTransactionPB,
 };
-use wavesexchange_log::{debug, error, warn};
+use wavesexchange_log::{debug, error, info, warn};
 
+use super::updates_archive::ArchiveWriter;
 use super::{
     epoch_ms_to_naivedatetime, BlockMicroblockAppend, BlockchainUpdate,
     BlockchainUpdatesWithLastHeight, Tx, UpdatesSource,
 };
 use crate::error::Error as AppError;
+use crate::metrics::ACTIVE_UPDATES_ENDPOINT;
+
+pub const MAX_MSG_SIZE: usize = 8 * 1024 * 1024; // 8 MB instead of the default 4 MB
 
 #[derive(Clone)]
 pub struct UpdatesSourceImpl {
+    endpoints: Arc<Vec<UpdatesEndpoint>>,
+    active_endpoint: Arc<AtomicUsize>,
+    stale_timeout: StdDuration,
+    recorder: Option<Arc<Mutex<ArchiveWriter>>>,
+}
+
+struct UpdatesEndpoint {
+    url: String,
     grpc_client: BlockchainUpdatesApiClient<tonic::transport::Channel>,
 }
 
-pub async fn new(blockchain_updates_url: &str) -> Result<UpdatesSourceImpl> {
+pub async fn new(
+    blockchain_updates_urls: &[String],
+    stale_timeout: Duration,
+    recorder: Option<ArchiveWriter>,
+) -> Result<UpdatesSourceImpl> {
+    let endpoints = blockchain_updates_urls
+        .iter()
+        .map(|url| {
+            // connection is established on subscribe, so an unavailable endpoint is just skipped
+            let channel = tonic::transport::Endpoint::from_shared(url.to_owned())?.connect_lazy();
+            ACTIVE_UPDATES_ENDPOINT
+                .with_label_values(&[url.as_str()])
+                .set(0);
+            Ok(UpdatesEndpoint {
+                url: url.to_owned(),
+                grpc_client: BlockchainUpdatesApiClient::new(channel)
+                    .max_decoding_message_size(MAX_MSG_SIZE),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     Ok(UpdatesSourceImpl {
-        grpc_client: {
-            const MAX_MSG_SIZE: usize = 8 * 1024 * 1024; // 8 MB instead of the default 4 MB
-            BlockchainUpdatesApiClient::connect(blockchain_updates_url.to_owned())
-                .await?
-                .max_decoding_message_size(MAX_MSG_SIZE)
-        },
+        endpoints: Arc::new(endpoints),
+        active_endpoint: Arc::new(AtomicUsize::new(0)),
+        stale_timeout: stale_timeout.to_std().unwrap(),
+        recorder: recorder.map(|r| Arc::new(Mutex::new(r))),
     })
 }
 
@@ -53,32 +87,33 @@ impl UpdatesSource for UpdatesSourceImpl {
     async fn stream(
         self,
         from_height: u32,
+        to_height: Option<u32>,
         batch_max_size: usize,
         batch_max_wait_time: Duration,
-    ) -> Result<Receiver<BlockchainUpdatesWithLastHeight>, AppError> {
-        let request = tonic::Request::new(SubscribeRequestPB {
-            from_height: from_height as i32,
-            to_height: 0,
-        });
+    ) -> Result<Receiver<Result<BlockchainUpdatesWithLastHeight, AppError>>, AppError> {
+        let (endpoint_idx, stream) = self.subscribe(from_height, to_height).await?;
 
-        let stream: tonic::Streaming<SubscribeEventPB> = self
-            .grpc_client
-            .clone()
-            .subscribe(request)
-            .await
-            .map_err(|e| AppError::StreamError(format!("Subscribe Stream error: {}", e)))?
-            .into_inner();
+        // every subscription is archived into its own files, so each file is a contiguous stream
+        self.finish_recording();
 
-        let (tx, rx) = channel::<BlockchainUpdatesWithLastHeight>(1);
+        let (tx, rx) = channel(1);
 
         tokio::spawn(async move {
             let r = self
-                .run(stream, tx, from_height, batch_max_size, batch_max_wait_time)
+                .run(
+                    stream,
+                    tx,
+                    endpoint_idx,
+                    from_height,
+                    to_height,
+                    batch_max_size,
+                    batch_max_wait_time,
+                )
                 .await;
             if let Err(e) = r {
                 error!("updates source stopped with error: {:?}", e);
             } else {
-                error!("updates source stopped without an error")
+                info!("updates source stopped")
             }
         });
 
@@ -87,11 +122,83 @@ impl UpdatesSource for UpdatesSourceImpl {
 }
 
 impl UpdatesSourceImpl {
+    /// Finishes the archive file being recorded, which adds it to the index
+    pub fn finish_recording(&self) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.lock().unwrap().finish() {
+                error!("cannot finish archive file: {:?}", e);
+            }
+        }
+    }
+
+    /// Subscribes to the active endpoint, falling over to the next ones in case of failure
+    async fn subscribe(
+        &self,
+        from_height: u32,
+        to_height: Option<u32>,
+    ) -> Result<(usize, tonic::Streaming<SubscribeEventPB>), AppError> {
+        let endpoints_count = self.endpoints.len();
+        let active_endpoint = self.active_endpoint.load(Ordering::SeqCst);
+        let mut last_error = None;
+
+        for endpoint_idx in
+            (active_endpoint..active_endpoint + endpoints_count).map(|idx| idx % endpoints_count)
+        {
+            let endpoint = &self.endpoints[endpoint_idx];
+            let request = tonic::Request::new(SubscribeRequestPB {
+                from_height: from_height as i32,
+                // 0 means no upper bound
+                to_height: to_height.unwrap_or(0) as i32,
+            });
+
+            match endpoint.grpc_client.clone().subscribe(request).await {
+                Ok(response) => {
+                    self.set_active_endpoint(endpoint_idx);
+                    info!("subscribed to {} from height {}", endpoint.url, from_height);
+                    return Ok((endpoint_idx, response.into_inner()));
+                }
+                Err(e) => {
+                    warn!("cannot subscribe to {}: {}", endpoint.url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(AppError::StreamError(format!(
+            "Subscribe Stream error: {}",
+            last_error.unwrap()
+        )))
+    }
+
+    fn set_active_endpoint(&self, endpoint_idx: usize) {
+        self.active_endpoint.store(endpoint_idx, Ordering::SeqCst);
+        self.endpoints
+            .iter()
+            .enumerate()
+            .for_each(|(idx, endpoint)| {
+                ACTIVE_UPDATES_ENDPOINT
+                    .with_label_values(&[endpoint.url.as_str()])
+                    .set((idx == endpoint_idx) as i64)
+            });
+    }
+
+    /// Makes the next endpoint active, so it is tried first on resubscribe
+    fn fail_over(&self, failed_endpoint_idx: usize) {
+        let next_endpoint_idx = (failed_endpoint_idx + 1) % self.endpoints.len();
+        warn!(
+            "failing over from {} to {}",
+            self.endpoints[failed_endpoint_idx].url, self.endpoints[next_endpoint_idx].url
+        );
+        self.set_active_endpoint(next_endpoint_idx);
+    }
+
     async fn run(
         &self,
         mut stream: tonic::Streaming<SubscribeEventPB>,
-        tx: Sender<BlockchainUpdatesWithLastHeight>,
+        tx: Sender<Result<BlockchainUpdatesWithLastHeight, AppError>>,
+        endpoint_idx: usize,
         from_height: u32,
+        to_height: Option<u32>,
         batch_max_size: usize,
         batch_max_wait_time: Duration,
     ) -> Result<(), AppError> {
@@ -104,13 +211,63 @@ impl UpdatesSourceImpl {
         let batch_max_wait_time = batch_max_wait_time.to_std().unwrap();
 
         loop {
-            if let Some(SubscribeEventPB {
+            let message = tokio::select! {
+                message = time::timeout(self.stale_timeout, stream.message()) => message,
+                _ = tx.closed() => {
+                    debug!("updates receiver was dropped, closing stream");
+                    return Ok(());
+                }
+            };
+
+            let message = match message {
+                Ok(Ok(Some(message))) => message,
+                Ok(Ok(None)) if to_height.is_some_and(|h| last_height >= h) => {
+                    // bounded stream is over, the pending batch is the last one
+                    if !result.is_empty() {
+                        tx.send(Ok(BlockchainUpdatesWithLastHeight {
+                            last_height,
+                            updates: result.drain(..).collect(),
+                        }))
+                        .await
+                        .map_err(|e| AppError::StreamError(format!("Channel error: {}", e)))?;
+                    }
+                    info!("target height {} reached", last_height);
+                    return Ok(());
+                }
+                Ok(Ok(None)) => {
+                    self.fail_over(endpoint_idx);
+                    return Err(AppError::StreamClosed(format!(
+                        "Updates stream was closed by {}",
+                        self.endpoints[endpoint_idx].url
+                    )));
+                }
+                Ok(Err(s)) => {
+                    self.fail_over(endpoint_idx);
+                    return Err(AppError::StreamError(format!(
+                        "Updates stream error: {}",
+                        s
+                    )));
+                }
+                Err(_) => {
+                    self.fail_over(endpoint_idx);
+                    return Err(AppError::StreamError(format!(
+                        "No updates from {} for {:?}",
+                        self.endpoints[endpoint_idx].url, self.stale_timeout
+                    )));
+                }
+            };
+
+            if let SubscribeEventPB {
                             update: Some(update),
-                        }) = stream
-                .message()
-                .await
-                .map_err(|s| AppError::StreamError(format!("Updates stream error: {}", s)))?
+                        } = message
             {
+                if let Some(recorder) = &self.recorder {
+                    // recording is best effort and must not stop ingestion
+                    if let Err(e) = recorder.lock().unwrap().write(&update) {
+                        error!("cannot archive update at height {}: {:?}", update.height, e);
+                    }
+                }
+
                 last_height = update.height as u32;
                 match BlockchainUpdate::try_from(update) {
                     Ok(upd) => {
@@ -128,18 +285,23 @@ impl UpdatesSourceImpl {
                             }
                         }
                         result.push(upd);
-                        Ok(())
                     }
-                    Err(err) => Err(err),
-                }?;
+                    Err(err) => {
+                        // the same update would come again after resubscribing
+                        tx.send(Err(err))
+                            .await
+                            .map_err(|e| AppError::StreamError(format!("Channel error: {}", e)))?;
+                        return Ok(());
+                    }
+                }
             }
 
             if !should_receive_more {
                 debug!("updating to height {}", last_height);
-                tx.send(BlockchainUpdatesWithLastHeight {
+                tx.send(Ok(BlockchainUpdatesWithLastHeight {
                     last_height,
                     updates: result.drain(..).collect(),
-                })
+                }))
                     .await
                     .map_err(|e| AppError::StreamError(format!("Channel error: {}", e)))?;
                 should_receive_more = true;
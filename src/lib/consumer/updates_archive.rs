@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use bytes::{Buf, Bytes};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use prost::Message;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use waves_protobuf_schemas::waves::events::BlockchainUpdated as BlockchainUpdatedPB;
+use wavesexchange_log::{info, warn};
+
+pub const INDEX_FILE_NAME: &str = "index.tsv";
+
+/// Writes `BlockchainUpdated` messages into gzipped length-delimited files,
+/// rotated by size. Every finished file is appended to the index as
+/// `<file name>\t<first height>\t<last height>`.
+pub struct ArchiveWriter {
+    dir: PathBuf,
+    max_file_size: usize,
+    current: Option<ArchiveFile>,
+}
+
+struct ArchiveFile {
+    name: String,
+    encoder: GzEncoder<BufWriter<File>>,
+    first_height: i32,
+    last_height: i32,
+    size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveIndexEntry {
+    pub file_name: String,
+    pub first_height: i32,
+    pub last_height: i32,
+}
+
+impl ArchiveWriter {
+    pub fn new(dir: impl AsRef<Path>, max_file_size: usize) -> Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Cannot create archive dir {}", dir.display()))?;
+
+        Ok(ArchiveWriter {
+            dir,
+            max_file_size,
+            current: None,
+        })
+    }
+
+    pub fn write(&mut self, update: &BlockchainUpdatedPB) -> Result<()> {
+        if self.current.is_none() {
+            self.current = Some(self.create_file(update.height)?);
+        }
+        let file = self.current.as_mut().unwrap();
+
+        let message = update.encode_length_delimited_to_vec();
+        file.encoder.write_all(&message)?;
+        file.size += message.len();
+        file.last_height = update.height;
+
+        if file.size >= self.max_file_size {
+            self.finish()?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the current file, so the next update starts a new one
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(file) = self.current.take() {
+            file.encoder.finish()?.flush()?;
+
+            let mut index = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.dir.join(INDEX_FILE_NAME))?;
+            writeln!(
+                index,
+                "{}\t{}\t{}",
+                file.name, file.first_height, file.last_height
+            )?;
+
+            info!(
+                "archived updates {}..={} into {}",
+                file.first_height, file.last_height, file.name
+            );
+        }
+
+        Ok(())
+    }
+
+    fn create_file(&self, first_height: i32) -> Result<ArchiveFile> {
+        // timestamp keeps names unique when the stream is resubscribed from the same height
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let name = format!("updates_{:010}_{}.pb.gz", first_height, created_at);
+        let file = File::create(self.dir.join(&name))?;
+
+        Ok(ArchiveFile {
+            name,
+            encoder: GzEncoder::new(BufWriter::new(file), Compression::default()),
+            first_height,
+            last_height: first_height,
+            size: 0,
+        })
+    }
+}
+
+impl Drop for ArchiveWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!("cannot finish archive file: {:?}", e);
+        }
+    }
+}
+
+pub fn read_index(dir: impl AsRef<Path>) -> Result<Vec<ArchiveIndexEntry>> {
+    let index_path = dir.as_ref().join(INDEX_FILE_NAME);
+    let index = fs::read_to_string(&index_path)
+        .with_context(|| format!("Cannot read {}", index_path.display()))?;
+
+    index
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split('\t');
+            let (Some(file_name), Some(first_height), Some(last_height)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                anyhow::bail!("Invalid index line '{}'", line);
+            };
+            Ok(ArchiveIndexEntry {
+                file_name: file_name.to_owned(),
+                first_height: first_height.parse()?,
+                last_height: last_height.parse()?,
+            })
+        })
+        .collect()
+}
+
+/// Reads length-delimited `BlockchainUpdated` messages, gunzipping `.gz` files.
+/// A truncated tail (e.g. the file being written when the process was killed) is skipped.
+pub fn read_updates_file(path: impl AsRef<Path>) -> Result<Vec<BlockchainUpdatedPB>> {
+    let path = path.as_ref();
+    let mut data = vec![];
+
+    if path.extension().map_or(false, |ext| ext == "gz") {
+        // on error, the bytes decompressed so far are kept in `data`
+        if let Err(e) = GzDecoder::new(File::open(path)?).read_to_end(&mut data) {
+            warn!("{} is truncated: {}", path.display(), e);
+        }
+    } else {
+        data = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    }
+
+    let mut buf = Bytes::from(data);
+    let mut updates = vec![];
+
+    while buf.has_remaining() {
+        match BlockchainUpdatedPB::decode_length_delimited(&mut buf) {
+            Ok(update) => updates.push(update),
+            Err(e) => {
+                warn!(
+                    "{} has an incomplete message at the end: {}",
+                    path.display(),
+                    e
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consumer::updates_mock::block;
+
+    fn archive_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    #[test]
+    fn written_files_are_indexed_and_read_back() {
+        let dir = archive_dir("archive_round_trip");
+        let updates = (1..=5)
+            .map(|height| block(height, &format!("B{}", height), 1_600_000_000_000, vec![]))
+            .collect::<Vec<_>>();
+        let message_size = updates[0].encode_length_delimited_to_vec().len();
+
+        // files are rotated after two updates, and the last one is finished on drop
+        let mut writer = ArchiveWriter::new(&dir, message_size * 2).unwrap();
+        for update in &updates {
+            writer.write(update).unwrap();
+        }
+        drop(writer);
+
+        let index = read_index(&dir).unwrap();
+        let ranges = index
+            .iter()
+            .map(|e| (e.first_height, e.last_height))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(1, 2), (3, 4), (5, 5)]);
+
+        let read = index
+            .iter()
+            .flat_map(|e| read_updates_file(dir.join(&e.file_name)).unwrap())
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(read, updates);
+    }
+
+    #[test]
+    fn truncated_file_is_read_up_to_the_last_complete_update() {
+        let dir = archive_dir("archive_truncated");
+        let mut writer = ArchiveWriter::new(&dir, usize::MAX).unwrap();
+        for height in 1..=3 {
+            writer
+                .write(&block(height, "B", 1_600_000_000_000, vec![]))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let file = dir.join(&read_index(&dir).unwrap()[0].file_name);
+        let mut data = vec![];
+        GzDecoder::new(File::open(&file).unwrap())
+            .read_to_end(&mut data)
+            .unwrap();
+        let plain = dir.join("updates.pb");
+        fs::write(&plain, &data[..data.len() - 1]).unwrap();
+
+        let read = read_updates_file(&plain).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(
+            read.iter().map(|u| u.height).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+}
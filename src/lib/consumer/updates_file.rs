@@ -0,0 +1,208 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Duration;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use wavesexchange_log::{debug, error, info};
+
+use super::updates_archive::{read_updates_file, INDEX_FILE_NAME};
+use super::{BlockchainUpdate, BlockchainUpdatesWithLastHeight, UpdatesSource};
+use crate::error::Error as AppError;
+
+/// Replays `BlockchainUpdated` messages recorded as length-delimited protobuf (optionally gzipped)
+/// from a file, or from all files of a directory (e.g. updates archive) in file name order.
+#[derive(Clone)]
+pub struct FileUpdatesSource {
+    files: Vec<PathBuf>,
+}
+
+pub fn new(path: impl AsRef<Path>) -> Result<FileUpdatesSource> {
+    let path = path.as_ref();
+    let files = if path.is_dir() {
+        let mut files = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|f| f.is_file() && !f.ends_with(INDEX_FILE_NAME));
+        files.sort();
+        files
+    } else {
+        vec![path.to_owned()]
+    };
+
+    Ok(FileUpdatesSource { files })
+}
+
+#[async_trait]
+impl UpdatesSource for FileUpdatesSource {
+    async fn stream(
+        self,
+        from_height: u32,
+        to_height: Option<u32>,
+        batch_max_size: usize,
+        _batch_max_wait_time: Duration,
+    ) -> Result<Receiver<Result<BlockchainUpdatesWithLastHeight, AppError>>, AppError> {
+        let (tx, rx) = channel(1);
+
+        tokio::spawn(async move {
+            match self.run(&tx, from_height, to_height, batch_max_size).await {
+                Ok(()) if to_height.is_some() => {
+                    info!("recorded updates up to target height were sent")
+                }
+                Ok(()) => {
+                    info!("all recorded updates were sent");
+                    // stay open like an idle node, otherwise the consumer resubscribes
+                    tx.closed().await;
+                }
+                Err(e) => {
+                    error!("file updates source stopped with error: {:?}", e);
+                    // the files would be read again with the same error after resubscribing
+                    tx.send(Err(e)).await.ok();
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+impl FileUpdatesSource {
+    async fn run(
+        &self,
+        tx: &Sender<Result<BlockchainUpdatesWithLastHeight, AppError>>,
+        from_height: u32,
+        to_height: Option<u32>,
+        batch_max_size: usize,
+    ) -> Result<(), AppError> {
+        let mut result = vec![];
+        let mut last_height = from_height;
+
+        for file in &self.files {
+            debug!("reading updates from {}", file.display());
+
+            let path = file.clone();
+            let updates = tokio::task::spawn_blocking(move || read_updates_file(path))
+                .await?
+                .map_err(|e| {
+                    AppError::InvalidMessage(format!(
+                        "Cannot read updates from {}: {:?}",
+                        file.display(),
+                        e
+                    ))
+                })?;
+
+            for update in updates {
+                if (update.height as u32) < from_height
+                    || to_height.is_some_and(|h| update.height as u32 > h)
+                {
+                    continue;
+                }
+
+                last_height = update.height as u32;
+                let upd = BlockchainUpdate::try_from(update)?;
+
+                // batches are cut the same way as for the GRPC stream
+                let should_send = match &upd {
+                    BlockchainUpdate::Block(_) => result.len() + 1 >= batch_max_size,
+                    BlockchainUpdate::Microblock(_) | BlockchainUpdate::Rollback(_) => true,
+                };
+                result.push(upd);
+
+                if should_send {
+                    send(tx, last_height, &mut result).await?;
+                }
+            }
+        }
+
+        if !result.is_empty() {
+            send(tx, last_height, &mut result).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn send(
+    tx: &Sender<Result<BlockchainUpdatesWithLastHeight, AppError>>,
+    last_height: u32,
+    result: &mut Vec<BlockchainUpdate>,
+) -> Result<(), AppError> {
+    debug!("updating to height {}", last_height);
+    tx.send(Ok(BlockchainUpdatesWithLastHeight {
+        last_height,
+        updates: result.drain(..).collect(),
+    }))
+    .await
+    .map_err(|e| AppError::StreamError(format!("Channel error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use waves_protobuf_schemas::waves::events::BlockchainUpdated as BlockchainUpdatedPB;
+
+    use super::*;
+    use crate::consumer::updates_archive::ArchiveWriter;
+    use crate::consumer::updates_mock::{b58, block, microblock};
+
+    /// Archive of the updates in a new temporary dir
+    fn archive(name: &str, updates: &[BlockchainUpdatedPB]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut writer = ArchiveWriter::new(&dir, usize::MAX).unwrap();
+        for update in updates {
+            writer.write(update).unwrap();
+        }
+        writer.finish().unwrap();
+        dir
+    }
+
+    async fn batch_ids(
+        source: FileUpdatesSource,
+        from_height: u32,
+        to_height: u32,
+    ) -> Result<Vec<Vec<String>>, AppError> {
+        let mut rx = source
+            .stream(from_height, Some(to_height), 2, Duration::seconds(1))
+            .await?;
+
+        let mut batches = vec![];
+        while let Some(batch) = rx.recv().await {
+            let ids = batch?.updates.into_iter().map(|u| match u {
+                BlockchainUpdate::Block(b) | BlockchainUpdate::Microblock(b) => b.id,
+                BlockchainUpdate::Rollback(id) => id,
+            });
+            batches.push(ids.collect());
+        }
+        Ok(batches)
+    }
+
+    #[tokio::test]
+    async fn recorded_updates_are_replayed_within_heights() {
+        let dir = archive(
+            "replayed_within_heights",
+            &[
+                block(1, "B1", 1_600_000_000_000, vec![]),
+                block(2, "B2", 1_600_000_060_000, vec![]),
+                block(3, "B3", 1_600_000_120_000, vec![]),
+                microblock(3, "M1", vec![]),
+                block(4, "B4", 1_600_000_180_000, vec![]),
+            ],
+        );
+
+        let batches = batch_ids(new(&dir).unwrap(), 2, 3).await.unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(batches, vec![vec![b58("B2"), b58("B3")], vec![b58("M1")]]);
+    }
+
+    #[tokio::test]
+    async fn unreadable_file_is_sent_as_error() {
+        let path = std::env::temp_dir().join("missing_updates.pb");
+
+        match batch_ids(new(&path).unwrap(), 1, 10).await {
+            Err(AppError::InvalidMessage(msg)) => assert!(msg.contains("missing_updates.pb")),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
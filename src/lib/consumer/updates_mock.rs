@@ -0,0 +1,456 @@
+//! Local `BlockchainUpdatesApi` server fed from scripted fixtures, for end-to-end tests
+//! of `updates::UpdatesSourceImpl` and `consumer::start`.
+//!
+//! Every subscription takes the next script. Once a script is over the stream stays open,
+//! like a node without new blocks, unless the script ends with a disconnect or an error.
+//! A bounded subscription (`to_height` > 0) is closed at the first update above `to_height`,
+//! or at the end of the script once `to_height` is sent.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Duration;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use waves_protobuf_schemas::tonic::{self, Request, Response, Status};
+use waves_protobuf_schemas::waves::{
+    block::Header as HeaderPB,
+    events::{
+        blockchain_updated::append::{
+            BlockAppend as BlockAppendPB, Body as BodyPB, MicroBlockAppend as MicroBlockAppendPB,
+        },
+        blockchain_updated::{Append as AppendPB, Rollback as RollbackPB, Update as UpdatePB},
+        grpc::{
+            blockchain_updates_api_server::{BlockchainUpdatesApi, BlockchainUpdatesApiServer},
+            GetBlockUpdateRequest, GetBlockUpdateResponse, GetBlockUpdatesRangeRequest,
+            GetBlockUpdatesRangeResponse, SubscribeEvent as SubscribeEventPB,
+            SubscribeRequest as SubscribeRequestPB,
+        },
+        transaction_metadata::{ExchangeMetadata, Metadata},
+        BlockchainUpdated as BlockchainUpdatedPB, StateUpdate as StateUpdatePB,
+        TransactionMetadata as TransactionMetadataPB,
+    },
+    signed_transaction::Transaction,
+    transaction::Data,
+    AssetPair, Block as BlockPB, ExchangeTransactionData, IssueTransactionData,
+    MicroBlock as MicroBlockPB, Order, SignedMicroBlock as SignedMicroBlockPB,
+    SignedTransaction as SignedTransactionPB, Transaction as WavesTx,
+};
+use wavesexchange_log::debug;
+
+use super::updates::{self, UpdatesSourceImpl, MAX_MSG_SIZE};
+
+pub const CHAIN_ID: u8 = b'T';
+
+#[derive(Debug, Clone)]
+pub enum MockEvent {
+    Update(BlockchainUpdatedPB),
+    /// Update at the given height which exceeds the client's `MAX_MSG_SIZE`
+    Oversized(i32),
+    /// Ends the stream gracefully
+    Disconnect,
+    /// Ends the stream with an error status
+    Error(Status),
+}
+
+#[derive(Debug, Clone)]
+pub struct MockTx {
+    pub id: Vec<u8>,
+    pub tx: SignedTransactionPB,
+    pub meta: TransactionMetadataPB,
+    pub state_update: StateUpdatePB,
+}
+
+pub struct MockUpdatesServer {
+    addr: SocketAddr,
+    subscriptions: Arc<Mutex<Vec<SubscribeRequestPB>>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+struct MockUpdatesService {
+    scripts: Arc<Mutex<VecDeque<Vec<MockEvent>>>>,
+    subscriptions: Arc<Mutex<Vec<SubscribeRequestPB>>>,
+}
+
+/// Starts the server on a random local port; it is stopped on drop
+pub async fn start(scripts: Vec<Vec<MockEvent>>) -> Result<MockUpdatesServer> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let subscriptions = Arc::new(Mutex::new(vec![]));
+    let service = MockUpdatesService {
+        scripts: Arc::new(Mutex::new(scripts.into())),
+        subscriptions: subscriptions.clone(),
+    };
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(BlockchainUpdatesApiServer::new(service))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                shutdown_rx.await.ok();
+            }),
+    );
+
+    Ok(MockUpdatesServer {
+        addr,
+        subscriptions,
+        shutdown: Some(shutdown_tx),
+    })
+}
+
+impl MockUpdatesServer {
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Updates source connected to the server
+    pub async fn connect(&self) -> UpdatesSourceImpl {
+        updates::new(&[self.url()], Duration::seconds(30), None)
+            .await
+            .unwrap()
+    }
+
+    /// `from_height` of every subscription received so far
+    pub fn subscriptions(&self) -> Vec<i32> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|s| s.from_height)
+            .collect()
+    }
+}
+
+impl Drop for MockUpdatesServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+#[async_trait]
+impl BlockchainUpdatesApi for MockUpdatesService {
+    type SubscribeStream = ReceiverStream<Result<SubscribeEventPB, Status>>;
+
+    async fn get_block_update(
+        &self,
+        _request: Request<GetBlockUpdateRequest>,
+    ) -> Result<Response<GetBlockUpdateResponse>, Status> {
+        Err(Status::unimplemented("not scripted"))
+    }
+
+    async fn get_block_updates_range(
+        &self,
+        _request: Request<GetBlockUpdatesRangeRequest>,
+    ) -> Result<Response<GetBlockUpdatesRangeResponse>, Status> {
+        Err(Status::unimplemented("not scripted"))
+    }
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequestPB>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let request = request.into_inner();
+        debug!("mock subscription from height {}", request.from_height);
+        let to_height = request.to_height;
+        self.subscriptions.lock().unwrap().push(request);
+
+        let script = self.scripts.lock().unwrap().pop_front().unwrap_or_default();
+        let (tx, rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            let mut last_height = 0;
+            for event in script {
+                let update = match event {
+                    MockEvent::Update(update) => update,
+                    MockEvent::Oversized(height) => BlockchainUpdatedPB {
+                        id: vec![0; MAX_MSG_SIZE + 1],
+                        ..block(height, "oversized", 0, vec![])
+                    },
+                    MockEvent::Disconnect => return,
+                    MockEvent::Error(status) => {
+                        tx.send(Err(status)).await.ok();
+                        return;
+                    }
+                };
+                if to_height > 0 && update.height > to_height {
+                    return;
+                }
+                last_height = update.height;
+                let event = SubscribeEventPB {
+                    update: Some(update),
+                };
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+            if to_height > 0 && last_height >= to_height {
+                return;
+            }
+            tx.closed().await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Append without a body and transactions to put into it
+fn append(txs: Vec<MockTx>) -> (AppendPB, Vec<SignedTransactionPB>) {
+    let mut append = AppendPB::default();
+    let mut signed_txs = vec![];
+    for tx in txs {
+        append.transaction_ids.push(tx.id);
+        append.transactions_metadata.push(tx.meta);
+        append.transaction_state_updates.push(tx.state_update);
+        signed_txs.push(tx.tx);
+    }
+    (append, signed_txs)
+}
+
+/// Block with the given id (as raw bytes) and timestamp in ms
+pub fn block(height: i32, id: &str, timestamp: i64, txs: Vec<MockTx>) -> BlockchainUpdatedPB {
+    let (mut append, transactions) = append(txs);
+    append.body = Some(BodyPB::Block(BlockAppendPB {
+        block: Some(BlockPB {
+            header: Some(HeaderPB {
+                timestamp,
+                ..Default::default()
+            }),
+            transactions,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    BlockchainUpdatedPB {
+        id: id.as_bytes().to_vec(),
+        height,
+        update: Some(UpdatePB::Append(append)),
+        ..Default::default()
+    }
+}
+
+/// Microblock with the given total block id (as raw bytes)
+pub fn microblock(height: i32, total_block_id: &str, txs: Vec<MockTx>) -> BlockchainUpdatedPB {
+    let (mut append, transactions) = append(txs);
+    append.body = Some(BodyPB::MicroBlock(MicroBlockAppendPB {
+        micro_block: Some(SignedMicroBlockPB {
+            micro_block: Some(MicroBlockPB {
+                transactions,
+                ..Default::default()
+            }),
+            total_block_id: total_block_id.as_bytes().to_vec(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    BlockchainUpdatedPB {
+        id: total_block_id.as_bytes().to_vec(),
+        height,
+        update: Some(UpdatePB::Append(append)),
+        ..Default::default()
+    }
+}
+
+/// Rollback to the block (or microblock) with the given id
+pub fn rollback(height: i32, id: &str) -> BlockchainUpdatedPB {
+    BlockchainUpdatedPB {
+        id: id.as_bytes().to_vec(),
+        height,
+        update: Some(UpdatePB::Rollback(RollbackPB::default())),
+        ..Default::default()
+    }
+}
+
+/// Drops metadata of all transactions in the append
+pub fn without_metadata(mut update: BlockchainUpdatedPB) -> BlockchainUpdatedPB {
+    if let Some(UpdatePB::Append(append)) = &mut update.update {
+        append.transactions_metadata.clear();
+    }
+    update
+}
+
+/// Issue transaction with the given id (as raw bytes)
+pub fn issue_tx(id: &str) -> MockTx {
+    MockTx {
+        id: id.as_bytes().to_vec(),
+        tx: SignedTransactionPB {
+            transaction: Some(Transaction::WavesTransaction(WavesTx {
+                chain_id: CHAIN_ID as i32,
+                sender_public_key: vec![1; 32],
+                timestamp: 1_600_000_000_000,
+                version: 3,
+                data: Some(Data::Issue(IssueTransactionData {
+                    name: "asset".to_owned(),
+                    amount: 1,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            proofs: vec![],
+        },
+        meta: TransactionMetadataPB {
+            sender_address: vec![2; 26],
+            ..Default::default()
+        },
+        state_update: StateUpdatePB::default(),
+    }
+}
+
+/// Exchange of asset `[1; 32]` for WAVES made at the timestamp (in ms)
+pub fn exchange_tx(id: &str, timestamp: i64) -> MockTx {
+    let order = Order {
+        asset_pair: Some(AssetPair {
+            amount_asset_id: vec![1; 32],
+            price_asset_id: vec![],
+        }),
+        amount: 10,
+        price: 2,
+        ..Default::default()
+    };
+    MockTx {
+        id: id.as_bytes().to_vec(),
+        tx: SignedTransactionPB {
+            transaction: Some(Transaction::WavesTransaction(WavesTx {
+                chain_id: CHAIN_ID as i32,
+                sender_public_key: vec![1; 32],
+                timestamp,
+                version: 2,
+                data: Some(Data::Exchange(ExchangeTransactionData {
+                    amount: 10,
+                    price: 2,
+                    orders: vec![order.clone(), order],
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            proofs: vec![],
+        },
+        meta: TransactionMetadataPB {
+            sender_address: vec![2; 26],
+            metadata: Some(Metadata::Exchange(ExchangeMetadata {
+                order_ids: vec![vec![3; 32], vec![4; 32]],
+                order_sender_addresses: vec![vec![5; 26], vec![6; 26]],
+                order_sender_public_keys: vec![vec![5; 32], vec![6; 32]],
+            })),
+            ..Default::default()
+        },
+        state_update: StateUpdatePB::default(),
+    }
+}
+
+/// Block a minute after the previous height
+pub fn block_at(height: i32, id: &str, txs: Vec<MockTx>) -> MockEvent {
+    MockEvent::Update(block(
+        height,
+        id,
+        1_600_000_000_000 + height as i64 * 60_000,
+        txs,
+    ))
+}
+
+/// Id of a scripted block as the consumer stores it
+pub fn b58(id: &str) -> String {
+    bs58::encode(id).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::Receiver;
+
+    use super::*;
+    use crate::consumer::{BlockchainUpdate, BlockchainUpdatesWithLastHeight, UpdatesSource};
+    use crate::error::Error as AppError;
+
+    fn update_ids(batch: &BlockchainUpdatesWithLastHeight) -> Vec<String> {
+        batch
+            .updates
+            .iter()
+            .map(|u| match u {
+                BlockchainUpdate::Block(b) | BlockchainUpdate::Microblock(b) => b.id.clone(),
+                BlockchainUpdate::Rollback(id) => format!("rollback to {}", id),
+            })
+            .collect()
+    }
+
+    async fn subscribe(
+        server: &MockUpdatesServer,
+        batch_max_size: usize,
+    ) -> Receiver<Result<BlockchainUpdatesWithLastHeight, AppError>> {
+        server
+            .connect()
+            .await
+            .stream(1, None, batch_max_size, Duration::seconds(30))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn blocks_are_batched_until_microblock_or_rollback() {
+        let server = start(vec![vec![
+            block_at(1, "B1", vec![]),
+            block_at(2, "B2", vec![]),
+            block_at(3, "B3", vec![]),
+            MockEvent::Update(microblock(3, "M1", vec![])),
+            MockEvent::Update(rollback(3, "B3")),
+        ]])
+        .await
+        .unwrap();
+
+        let mut rx = subscribe(&server, 2).await;
+
+        let batch = rx.recv().await.unwrap().unwrap();
+        assert_eq!(update_ids(&batch), vec![b58("B1"), b58("B2")]);
+        assert_eq!(batch.last_height, 2);
+
+        let batch = rx.recv().await.unwrap().unwrap();
+        assert_eq!(update_ids(&batch), vec![b58("B3"), b58("M1")]);
+
+        let batch = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            update_ids(&batch),
+            vec![format!("rollback to {}", b58("B3"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn transactions_without_metadata_are_skipped() {
+        let server = start(vec![vec![MockEvent::Update(without_metadata(block(
+            1,
+            "B1",
+            1_600_000_000_000,
+            vec![issue_tx("T1"), issue_tx("T2")],
+        )))]])
+        .await
+        .unwrap();
+
+        let mut rx = subscribe(&server, 1).await;
+
+        let batch = rx.recv().await.unwrap().unwrap();
+        match &batch.updates[..] {
+            [BlockchainUpdate::Block(b)] => assert!(b.txs.is_empty()),
+            updates => panic!("unexpected updates {:?}", updates),
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_message_stops_the_stream() {
+        let server = start(vec![vec![
+            block_at(1, "B1", vec![]),
+            MockEvent::Oversized(2),
+        ]])
+        .await
+        .unwrap();
+
+        let mut rx = subscribe(&server, 1).await;
+
+        let batch = rx.recv().await.unwrap().unwrap();
+        assert_eq!(update_ids(&batch), vec![b58("B1")]);
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -35,6 +35,9 @@ pub enum Error {
 
     #[error("InconsistDataError: {0}")]
     InconsistDataError(String),
+
+    #[error("TxUidError: {0}")]
+    TxUidError(String),
 }
 
 // impl done manually because InteractError is not Sync
@@ -5,6 +5,7 @@ pub mod config;
 pub mod consumer;
 pub mod db;
 pub mod error;
+pub mod metrics;
 pub mod models;
 pub mod schema;
 mod tuple_len;
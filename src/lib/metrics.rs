@@ -0,0 +1,72 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+};
+
+lazy_static! {
+    pub static ref ACTIVE_UPDATES_ENDPOINT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "consumer_active_updates_endpoint",
+            "Blockchain updates endpoint the consumer is subscribed to (1 - active, 0 - standby)"
+        ),
+        &["url"]
+    )
+    .unwrap();
+    pub static ref LAST_COMMITTED_HEIGHT: IntGauge = IntGauge::new(
+        "consumer_last_committed_height",
+        "Height of the last update saved to the database"
+    )
+    .unwrap();
+    pub static ref BLOCK_TIMESTAMP_LAG: Gauge = Gauge::new(
+        "consumer_block_timestamp_lag_seconds",
+        "Time between the last saved block (microblock) timestamp and its commit"
+    )
+    .unwrap();
+    pub static ref UPDATES_PER_BATCH: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "consumer_updates_per_batch",
+            "Count of blockchain updates handled in one database transaction"
+        )
+        .buckets(vec![1., 2., 5., 10., 25., 50., 100., 250., 500., 1000.])
+    )
+    .unwrap();
+    pub static ref TXS_INGESTED: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "consumer_txs_ingested_total",
+            "Transactions converted and saved, by type"
+        ),
+        &["type"]
+    )
+    .unwrap();
+    pub static ref TXS_SKIPPED: IntCounter = IntCounter::new(
+        "consumer_txs_skipped_total",
+        "Transactions skipped due to inconsistent data"
+    )
+    .unwrap();
+    pub static ref ROLLBACKS: IntCounter =
+        IntCounter::new("consumer_rollbacks_total", "Rollbacks handled").unwrap();
+    pub static ref ROLLBACK_DEPTH: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "consumer_rollback_depth_blocks",
+            "Count of heights removed by a rollback"
+        )
+        .buckets(vec![0., 1., 2., 5., 10., 25., 50., 100., 500., 1000.])
+    )
+    .unwrap();
+    pub static ref DB_TRANSACTION_DURATION: Histogram = Histogram::with_opts(HistogramOpts::new(
+        "consumer_db_transaction_duration_seconds",
+        "Duration of the database transaction saving a batch of updates"
+    ))
+    .unwrap();
+    pub static ref CANDLES_CALCULATION_DURATION: Histogram =
+        Histogram::with_opts(HistogramOpts::new(
+            "consumer_candles_calculation_duration_seconds",
+            "Duration of candles (and pairs) recalculation"
+        ))
+        .unwrap();
+    pub static ref CANDLES_DEFERRED: IntGauge = IntGauge::new(
+        "consumer_candles_deferred",
+        "1 while candles calculation is deferred until the consumer catches up"
+    )
+    .unwrap();
+}
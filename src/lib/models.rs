@@ -35,6 +35,7 @@ pub enum DataEntryTypeValue {
     Integer(i64),
     String(String),
     List(Value),
+    Case(Value),
 }
 
 impl From<&InvokeScriptArgValue> for DataEntryTypeValue {
@@ -51,11 +52,98 @@ impl From<&InvokeScriptArgValue> for DataEntryTypeValue {
             InvokeScriptArgValue::BooleanValue(v) => DataEntryTypeValue::Boolean(*v),
             // deep conversion of List
             InvokeScriptArgValue::List(v) => DataEntryTypeValue::List(json!(ArgList::from(v))),
-            InvokeScriptArgValue::CaseObj(_) => todo!(),
+            // deep conversion of serialized Ride CaseObj,
+            // payload that cannot be decoded is kept as raw bytes
+            InvokeScriptArgValue::CaseObj(v) => decode_case_obj(v).unwrap_or_else(|_| {
+                #[allow(deprecated)] // for base64::encode()
+                DataEntryTypeValue::Binary(format!("base64:{}", base64::encode(v)))
+            }),
         }
     }
 }
 
+// tags of evaluated terms in Ride serialization
+const E_LONG: u8 = 0;
+const E_BYTES: u8 = 1;
+const E_STRING: u8 = 2;
+const E_TRUE: u8 = 6;
+const E_FALSE: u8 = 7;
+const E_ARR: u8 = 11;
+const E_CASEOBJ: u8 = 12;
+
+/// Decodes Ride-serialized CaseObj into `{"name": <type name>, "fields": {<field>: <typed value>}}`
+pub fn decode_case_obj(bytes: &[u8]) -> Result<DataEntryTypeValue, String> {
+    let mut rest = bytes;
+    let value = decode_evaluated(&mut rest)?;
+    if !rest.is_empty() {
+        return Err(format!("{} trailing bytes after CaseObj", rest.len()));
+    }
+    match value {
+        DataEntryTypeValue::Case(_) => Ok(value),
+        v => Err(format!("expected CaseObj, got {:?}", v)),
+    }
+}
+
+fn decode_evaluated(bytes: &mut &[u8]) -> Result<DataEntryTypeValue, String> {
+    let tag = take_bytes(bytes, 1)?[0];
+    let value = match tag {
+        E_LONG => {
+            let v = take_bytes(bytes, 8)?;
+            DataEntryTypeValue::Integer(i64::from_be_bytes(v.try_into().unwrap()))
+        }
+        E_BYTES => {
+            let len = take_len(bytes)?;
+            #[allow(deprecated)] // for base64::encode()
+            DataEntryTypeValue::Binary(format!(
+                "base64:{}",
+                base64::encode(take_bytes(bytes, len)?)
+            ))
+        }
+        E_STRING => DataEntryTypeValue::String(escape_unicode_null(&take_string(bytes)?)),
+        E_TRUE => DataEntryTypeValue::Boolean(true),
+        E_FALSE => DataEntryTypeValue::Boolean(false),
+        E_ARR => {
+            let count = take_len(bytes)?;
+            let items = (0..count)
+                .map(|_| decode_evaluated(bytes))
+                .collect::<Result<Vec<_>, _>>()?;
+            DataEntryTypeValue::List(json!(ArgList(items)))
+        }
+        E_CASEOBJ => {
+            let name = take_string(bytes)?;
+            let fields_count = take_len(bytes)?;
+            let mut fields = serde_json::Map::new();
+            for _ in 0..fields_count {
+                let field_name = take_string(bytes)?;
+                let field_value = decode_evaluated(bytes)?;
+                fields.insert(field_name, json!(field_value));
+            }
+            DataEntryTypeValue::Case(json!({ "name": name, "fields": fields }))
+        }
+        t => return Err(format!("unexpected term tag {t}")),
+    };
+    Ok(value)
+}
+
+fn take_bytes<'b>(bytes: &mut &'b [u8], len: usize) -> Result<&'b [u8], String> {
+    if bytes.len() < len {
+        return Err(format!("expected {} bytes, got {}", len, bytes.len()));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn take_len(bytes: &mut &[u8]) -> Result<usize, String> {
+    let len = i32::from_be_bytes(take_bytes(bytes, 4)?.try_into().unwrap());
+    usize::try_from(len).map_err(|_| format!("negative length {len}"))
+}
+
+fn take_string(bytes: &mut &[u8]) -> Result<String, String> {
+    let len = take_len(bytes)?;
+    String::from_utf8(take_bytes(bytes, len)?.to_vec()).map_err(|e| e.to_string())
+}
+
 #[derive(Debug, Serialize)]
 pub struct ArgList(pub Vec<DataEntryTypeValue>);
 
@@ -246,4 +334,61 @@ mod tests {
             panic!("Wrong variant: {:?}", src);
         }
     }
+
+    fn ride_string(s: &str) -> Vec<u8> {
+        let mut bytes = (s.len() as i32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn serialize_case_obj() {
+        // AssetPair(amountAsset = base58'', price = [7, true], inner = Unit)
+        let mut bytes = vec![E_CASEOBJ];
+        bytes.extend(ride_string("AssetPair"));
+        bytes.extend(3i32.to_be_bytes());
+        bytes.extend(ride_string("amountAsset"));
+        bytes.push(E_BYTES);
+        bytes.extend(2i32.to_be_bytes());
+        bytes.extend([0, 1]);
+        bytes.extend(ride_string("price"));
+        bytes.push(E_ARR);
+        bytes.extend(2i32.to_be_bytes());
+        bytes.push(E_LONG);
+        bytes.extend(7i64.to_be_bytes());
+        bytes.push(E_TRUE);
+        bytes.extend(ride_string("inner"));
+        bytes.push(E_CASEOBJ);
+        bytes.extend(ride_string("Unit"));
+        bytes.extend(0i32.to_be_bytes());
+
+        let data_value = DataEntryTypeValue::from(&InvokeScriptArgValue::CaseObj(bytes));
+        let json = json!(data_value);
+        let expected = json!({
+            "type": "case",
+            "value": {
+                "name": "AssetPair",
+                "fields": {
+                    "amountAsset": {"type": "binary", "value": "base64:AAE="},
+                    "price": {"type": "list", "value": [
+                        {"type": "integer", "value": 7},
+                        {"type": "boolean", "value": true},
+                    ]},
+                    "inner": {"type": "case", "value": {"name": "Unit", "fields": {}}},
+                },
+            },
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn serialize_malformed_case_obj() {
+        let bytes = vec![E_CASEOBJ, 0, 0, 0, 9, b'A'];
+        let data_value = DataEntryTypeValue::from(&InvokeScriptArgValue::CaseObj(bytes));
+        assert_eq!(
+            json!(data_value),
+            json!({"type": "binary", "value": "base64:DAAAAAlB"})
+        );
+        assert!(decode_case_obj(&[E_LONG, 0, 0, 0, 0, 0, 0, 0, 1]).is_err());
+    }
 }
@@ -1,5 +1,17 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    aliases (alias) {
+        alias -> Text,
+        address -> Text,
+        qualified_alias -> Text,
+        tx_uid -> Int8,
+        block_uid -> Int8,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -47,7 +59,10 @@ diesel::table! {
 diesel::table! {
     use diesel::sql_types::*;
 
-    assets_metadata (asset_id) {
+    assets_metadata (superseded_by, asset_id) {
+        uid -> Int8,
+        superseded_by -> Int8,
+        block_uid -> Int8,
         asset_id -> Varchar,
         asset_name -> Nullable<Varchar>,
         ticker -> Nullable<Varchar>,
@@ -55,6 +70,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    balance_history (superseded_by, address, asset_id) {
+        uid -> Int8,
+        superseded_by -> Int8,
+        block_uid -> Int8,
+        tx_uid -> Int8,
+        address -> Text,
+        asset_id -> Text,
+        amount_before -> Int8,
+        amount_after -> Int8,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -89,6 +119,53 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    data_entries (superseded_by, address, key) {
+        uid -> Int8,
+        superseded_by -> Int8,
+        block_uid -> Int8,
+        address -> Text,
+        key -> Text,
+        data_type -> Nullable<Text>,
+        value_integer -> Nullable<Int8>,
+        value_boolean -> Nullable<Bool>,
+        value_binary -> Nullable<Text>,
+        value_string -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    ingest_events (seq) {
+        seq -> Int8,
+        event_type -> Text,
+        block_uid -> Int8,
+        height -> Int4,
+        payload -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    leases (superseded_by, lease_id) {
+        uid -> Int8,
+        superseded_by -> Int8,
+        block_uid -> Int8,
+        lease_id -> Text,
+        status -> Text,
+        origin_transaction_id -> Text,
+        sender -> Text,
+        sender_public_key -> Text,
+        recipient -> Text,
+        amount -> Int8,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -347,7 +424,7 @@ diesel::table! {
         arg_value_list -> Nullable<Jsonb>,
         position_in_args -> Int2,
         tx_uid -> Int8,
-        height -> Nullable<Int4>,
+        height -> Int4,
     }
 }
 
@@ -358,7 +435,7 @@ diesel::table! {
         tx_uid -> Int8,
         amount -> Int8,
         position_in_payment -> Int2,
-        height -> Nullable<Int4>,
+        height -> Int4,
         asset_id -> Varchar,
     }
 }
@@ -420,7 +497,7 @@ diesel::table! {
         arg_value_list -> Nullable<Jsonb>,
         position_in_args -> Int2,
         tx_uid -> Int8,
-        height -> Nullable<Int4>,
+        height -> Int4,
     }
 }
 
@@ -431,7 +508,7 @@ diesel::table! {
         tx_uid -> Int8,
         amount -> Int8,
         position_in_payment -> Int2,
-        height -> Nullable<Int4>,
+        height -> Int4,
         asset_id -> Varchar,
     }
 }
@@ -647,12 +724,17 @@ diesel::joinable!(txs_18_args -> txs_18 (tx_uid));
 diesel::joinable!(txs_18_payment -> txs_18 (tx_uid));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    aliases,
     asset_origins,
     asset_tickers,
     asset_updates,
     assets_metadata,
+    balance_history,
     blocks_microblocks,
     candles,
+    data_entries,
+    ingest_events,
+    leases,
     pairs,
     txs,
     txs_1,